@@ -0,0 +1,22 @@
+CREATE TABLE IF NOT EXISTS draft_orders (
+    owner_id   uuid        NOT NULL,
+    id         uuid        NOT NULL,
+    created_at timestamptz NOT NULL,
+    active_at  timestamptz NOT NULL,
+    PRIMARY KEY (owner_id),
+    FOREIGN KEY (owner_id) REFERENCES users (id)
+        ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS draft_files (
+    id         uuid     NOT NULL,
+    owner_id   uuid     NOT NULL,
+    filetype   filetype NOT NULL,
+    filesize   bigint   NOT NULL,
+    object_key text     NOT NULL,
+    upload_id  text,
+    index      integer  NOT NULL,
+    PRIMARY KEY (id),
+    FOREIGN KEY (owner_id) REFERENCES draft_orders (owner_id)
+        ON DELETE CASCADE
+);
@@ -0,0 +1,6 @@
+ALTER TABLE settings
+    ADD COLUMN IF NOT EXISTS completed_file_retention_days integer NOT NULL DEFAULT 7,
+    ADD COLUMN IF NOT EXISTS rejected_file_retention_days  integer NOT NULL DEFAULT 1,
+    ADD COLUMN IF NOT EXISTS cancelled_file_retention_days integer NOT NULL DEFAULT 1;
+
+ALTER TABLE files ADD COLUMN IF NOT EXISTS purged_at timestamptz;
@@ -0,0 +1,3 @@
+ALTER TABLE order_status_updates
+    ADD COLUMN IF NOT EXISTS reason text,
+    ADD COLUMN IF NOT EXISTS note   text;
@@ -0,0 +1,5 @@
+ALTER TABLE order_status_updates
+    ADD COLUMN IF NOT EXISTS actor_id uuid REFERENCES users (id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS order_status_updates_actor_id_fkey_idx
+ON order_status_updates USING btree (actor_id);
@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS order_edits (
+    id         bigint      NOT NULL GENERATED ALWAYS AS IDENTITY,
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    order_id   uuid        NOT NULL,
+    actor_id   uuid,
+    previous   jsonb       NOT NULL,
+    changes    jsonb       NOT NULL,
+    PRIMARY KEY (id),
+    FOREIGN KEY (order_id) REFERENCES orders (id)
+        ON DELETE CASCADE,
+    FOREIGN KEY (actor_id) REFERENCES users (id)
+        ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS order_edits_order_id_fkey_idx
+ON order_edits USING btree (order_id);
@@ -0,0 +1,23 @@
+CREATE TABLE IF NOT EXISTS lamination_options (
+    created_at     timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at     timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    is_serviceable boolean     NOT NULL DEFAULT false
+);
+
+CREATE TABLE IF NOT EXISTS lamination_paper_variants (
+    paper_variant_id integer     NOT NULL,
+    created_at       timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (paper_variant_id),
+    FOREIGN KEY (paper_variant_id) REFERENCES paper_variants (id)
+        ON DELETE CASCADE
+);
+
+INSERT INTO lamination_options (is_serviceable)
+SELECT COALESCE((SELECT is_lamination_serviceable FROM settings LIMIT 1), false);
+
+INSERT INTO lamination_paper_variants (paper_variant_id)
+SELECT id FROM paper_variants WHERE is_laminatable;
+
+ALTER TABLE settings DROP COLUMN IF EXISTS is_lamination_serviceable;
+ALTER TABLE paper_variants DROP COLUMN IF EXISTS is_laminatable;
+ALTER TYPE paper_variant DROP ATTRIBUTE IF EXISTS is_laminatable;
@@ -0,0 +1,16 @@
+CREATE TYPE binding_colour AS (
+    id           integer,
+    colour       text,
+    is_available boolean
+);
+
+CREATE TYPE binding_paper AS (
+    paper_id  integer,
+    coverable boolean
+);
+
+ALTER TABLE binding_colours ADD CONSTRAINT binding_colours_binding_id_colour_key
+UNIQUE (binding_id, colour);
+
+CREATE INDEX IF NOT EXISTS bindings_papers_paper_id_fkey_idx
+ON bindings_papers USING btree (paper_id);
@@ -0,0 +1,20 @@
+CREATE TABLE IF NOT EXISTS lamination_films (
+    id           integer     NOT NULL GENERATED ALWAYS AS IDENTITY,
+    created_at   timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    name         text        NOT NULL,
+    is_available boolean     NOT NULL DEFAULT true,
+    PRIMARY KEY (id),
+    UNIQUE (name)
+);
+
+ALTER TABLE services ADD COLUMN IF NOT EXISTS lamination_film_id integer;
+
+ALTER TABLE services ADD CONSTRAINT services_lamination_film_id_fkey
+FOREIGN KEY (lamination_film_id) REFERENCES lamination_films (id)
+    ON DELETE SET NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS lamination_films_pkey_idx
+ON lamination_films USING btree (id);
+
+CREATE INDEX IF NOT EXISTS services_lamination_film_id_fkey_idx
+ON services USING btree (lamination_film_id);
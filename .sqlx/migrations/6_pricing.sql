@@ -0,0 +1,20 @@
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS quote bigint;
+
+CREATE TABLE IF NOT EXISTS paper_variant_rates (
+    paper_variant_id integer     NOT NULL,
+    updated_at       timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    sheet_rate       bigint      NOT NULL DEFAULT 0,
+    mono_side_rate   bigint      NOT NULL DEFAULT 0,
+    colour_side_rate bigint      NOT NULL DEFAULT 0,
+    PRIMARY KEY (paper_variant_id),
+    FOREIGN KEY (paper_variant_id) REFERENCES paper_variants (id)
+        ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS service_rates (
+    type       service_type NOT NULL,
+    updated_at timestamptz  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    base_rate  bigint       NOT NULL DEFAULT 0,
+    sheet_rate bigint       NOT NULL DEFAULT 0,
+    PRIMARY KEY (type)
+);
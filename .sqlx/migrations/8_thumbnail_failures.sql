@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS thumbnail_failures (
+    id         bigint      NOT NULL GENERATED ALWAYS AS IDENTITY,
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    object_key text        NOT NULL,
+    filetype   filetype    NOT NULL,
+    page       integer,
+    attempts   integer     NOT NULL,
+    reason     text        NOT NULL,
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX IF NOT EXISTS thumbnail_failures_object_key_idx
+ON thumbnail_failures USING btree (object_key);
@@ -0,0 +1,3 @@
+ALTER TYPE filetype ADD VALUE IF NOT EXISTS 'docx';
+ALTER TYPE filetype ADD VALUE IF NOT EXISTS 'heic';
+ALTER TYPE filetype ADD VALUE IF NOT EXISTS 'webp';
@@ -10,7 +10,10 @@ use tracing_subscriber::Layer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 use graphein_app::expand_router;
-use graphein_common::{AppState, Config, R2Bucket, Thumbnailer, daemons::DaemonController};
+use graphein_common::{
+    AppState, Bucket, Config, LocalStore, S3Store, StorageConfig, Thumbnailer,
+    daemons::DaemonController,
+};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -59,22 +62,43 @@ async fn main() -> Result<()> {
 
     let http = ReqwestClient::new();
 
-    let (thumbnailer, thumbnailer_rx) = Thumbnailer::new();
-
-    let app_state = AppState::new(
-        config.clone(),
-        pool,
-        http.clone(),
-        R2Bucket::new(
-            http,
-            config.r2_account_id(),
-            config.r2_bucket_name().to_owned(),
-            config.r2_access_key_id(),
-            config.r2_secret_access_key(),
-        )?,
-        thumbnailer,
-    );
+    let bucket = match config.storage() {
+        StorageConfig::S3 {
+            endpoint,
+            path_style,
+            region,
+            bucket_name,
+            access_key_id,
+            secret_access_key,
+        } => Bucket::new(
+            S3Store::new(
+                http.clone(),
+                endpoint,
+                *path_style,
+                region.clone(),
+                bucket_name.clone(),
+                access_key_id,
+                secret_access_key,
+            )?,
+            config.preview_page_limit(),
+        ),
+        StorageConfig::Local { directory } => {
+            tracing::warn!("Storing files locally under `{}`", directory.display());
+            Bucket::new(
+                LocalStore::new(
+                    directory.clone(),
+                    config.root_uri(),
+                    config.secret().as_bytes(),
+                )?,
+                config.preview_page_limit(),
+            )
+        }
+    };
+    let (thumbnailer, thumbnailer_rx) = Thumbnailer::new(config.thumbnailer_queue_size());
+
+    let app_state = AppState::new(config.clone(), pool, http, bucket, thumbnailer);
     app_state.load_sessions().await?;
+    app_state.load_draft_orders().await?;
 
     let daemon_controller =
         DaemonController::new(app_state.clone()).start_all(Handle::current(), thumbnailer_rx);
@@ -121,6 +145,10 @@ async fn shutdown_signal(daemon_controller: DaemonController, app_state: AppStat
     }
 
     daemon_controller.stop_all();
+    // Sessions are still committed if the draft orders cannot be
+    if let Err(err) = app_state.draft_orders.commit(app_state.pool.clone()).await {
+        tracing::error!(?err, "failed to commit draft orders to database");
+    }
     app_state
         .sessions
         .commit(app_state.pool)
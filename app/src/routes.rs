@@ -7,7 +7,7 @@ use axum::{
     routing::get,
 };
 use http::{HeaderValue, Method};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tower_http::{
     cors::{AllowHeaders, CorsLayer},
     trace::TraceLayer,
@@ -24,6 +24,7 @@ mod events;
 mod merchant;
 mod opts;
 mod orders;
+mod storage;
 mod user;
 
 #[doc(hidden)]
@@ -31,18 +32,26 @@ mod user;
 pub fn expand_router(state: AppState) -> Router<AppState> {
     Router::new()
         .route("/_internal/healthcheck", get(get_healthcheck))
+        .route("/_internal/metrics", get(get_metrics))
         .nest("/auth", auth::expand_router(state.clone()))
         .nest("/user", user::expand_router(state.clone()))
         .nest("/orders", orders::expand_router(state.clone()))
         .nest("/merchant", merchant::expand_router(state.clone()))
         .nest("/opts", opts::expand_router(state.clone()))
         .nest("/events", events::expand_router(state.clone()))
+        .nest("/storage", storage::expand_router())
         .fallback(get(async || {
             AppError::NotFound(NotFoundError::PathNotFound)
         }))
         .route_layer(
             CorsLayer::new()
-                .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
+                .allow_methods([
+                    Method::GET,
+                    Method::POST,
+                    Method::PUT,
+                    Method::PATCH,
+                    Method::DELETE,
+                ])
                 .allow_origin(
                     state
                         .config
@@ -94,3 +103,40 @@ async fn get_healthcheck(
 
     Ok(ResponseBuilder::new().data("ok").build())
 }
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Metrics {
+    thumbnailer: ThumbnailerMetrics,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ThumbnailerMetrics {
+    queue_depth: usize,
+    queue_size: usize,
+    workers: usize,
+}
+
+async fn get_metrics(
+    State(AppState {
+        config,
+        thumbnailer,
+        ..
+    }): State<AppState>,
+    QsQuery(HealthCheckQueryParams { ref token }): QsQuery<HealthCheckQueryParams>,
+) -> HandlerResponse<Metrics> {
+    if token != config.healthcheck_token() {
+        return Err(AppError::NotFound(NotFoundError::PathNotFound));
+    }
+
+    Ok(ResponseBuilder::new()
+        .data(Metrics {
+            thumbnailer: ThumbnailerMetrics {
+                queue_depth: thumbnailer.queue_depth(),
+                queue_size: thumbnailer.queue_size(),
+                workers: config.thumbnailer_workers(),
+            },
+        })
+        .build())
+}
@@ -1,6 +1,23 @@
-use axum::{Router, middleware, routing::get};
+use std::{collections::HashSet, future};
 
-use graphein_common::{AppState, HandlerResponse, middleware::requires_onboarding};
+use axum::{
+    Error as AxumError, Router,
+    extract::State,
+    middleware,
+    response::sse::{Event, KeepAlive, Sse},
+    routing::get,
+};
+use futures::stream::{self, Stream, StreamExt as _};
+use http::HeaderMap;
+use serde_json::json;
+
+use graphein_common::{
+    AppError, AppState,
+    auth::Session,
+    database::OrdersTable,
+    middleware::{merchant_only, requires_onboarding},
+    schemas::{IncomingOrderEvent, enums::UserRole},
+};
 
 pub(super) fn expand_router(state: AppState) -> Router<AppState> {
     Router::new()
@@ -10,15 +27,72 @@ pub(super) fn expand_router(state: AppState) -> Router<AppState> {
         )
         .route(
             "/merchant/incoming-orders",
-            get(get_events_merchant_incoming_orders),
+            get(get_events_merchant_incoming_orders)
+                .route_layer(middleware::from_fn_with_state(state.clone(), merchant_only)),
         )
         .route_layer(middleware::from_fn_with_state(state, requires_onboarding))
 }
 
-async fn get_events_order_status_changes() -> HandlerResponse<()> {
-    todo!()
+async fn get_events_order_status_changes(
+    State(AppState { order_events, .. }): State<AppState>,
+    Session {
+        user_id, user_role, ..
+    }: Session,
+) -> Sse<impl Stream<Item = Result<Event, AxumError>>> {
+    let stream = order_events
+        .subscribe_status_changes()
+        .filter(move |change| {
+            future::ready(matches!(user_role, UserRole::Merchant) || change.owner_id == user_id)
+        })
+        .map(|change| Event::default().event("status-change").json_data(change));
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
 }
 
-async fn get_events_merchant_incoming_orders() -> HandlerResponse<()> {
-    todo!()
+async fn get_events_merchant_incoming_orders(
+    State(AppState {
+        pool, order_events, ..
+    }): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, AxumError>>>, AppError> {
+    // Subscribe before replaying so that no event slips through in between. Replayed events may
+    // overlap with the ones already received, which clients deduplicate by their IDs
+    let live = order_events.subscribe_incoming_orders();
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<i64>().ok());
+    let replayed = match last_event_id {
+        Some(last_event_id) => {
+            OrdersTable::fetch_incoming_order_events_since(
+                &mut *(pool.acquire().await?),
+                last_event_id,
+            )
+            .await?
+        }
+        None => Vec::new(),
+    };
+
+    let replayed_ids = replayed
+        .iter()
+        .map(IncomingOrderEvent::id)
+        .collect::<HashSet<_>>();
+    let stream = stream::iter(replayed)
+        .chain(live.filter(move |event| future::ready(!replayed_ids.contains(&event.id()))))
+        .map(|event| {
+            let id = event.id().to_string();
+            match event {
+                IncomingOrderEvent::Added { order, .. } => {
+                    Event::default().id(id).event("added").json_data(order)
+                }
+                IncomingOrderEvent::Removed {
+                    order_id, status, ..
+                } => Event::default()
+                    .id(id)
+                    .event("removed")
+                    .json_data(json!({ "orderId": order_id, "status": status })),
+            }
+        });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
 }
@@ -7,7 +7,7 @@ use graphein_common::{
     extract::QsQuery,
     middleware::{merchant_only, requires_onboarding},
     response::ResponseBuilder,
-    schemas::{CompactOrder, MerchantOrdersGlance, enums::OrderStatus},
+    schemas::{CompactOrder, MerchantOrdersGlance, UserId, enums::OrderStatus},
 };
 use serde::Deserialize;
 
@@ -59,20 +59,26 @@ async fn get_merchant_orders_glance(
 }
 
 #[derive(Default, Debug, Deserialize)]
-#[serde(default)]
-struct OrderStatuses {
+#[serde(default, rename_all = "camelCase")]
+struct OrderHistoryFilters {
     statuses: Option<Vec<OrderStatus>>,
+    actor_id: Option<UserId>,
 }
 
 async fn get_merchant_orders_history(
     State(AppState { pool, .. }): State<AppState>,
     QsQuery(RequestData {
-        data: OrderStatuses { statuses },
+        data: OrderHistoryFilters { statuses, actor_id },
         pagination,
-    }): QsQuery<RequestData<OrderStatuses>>,
+    }): QsQuery<RequestData<OrderHistoryFilters>>,
 ) -> HandlerResponse<Vec<CompactOrder>> {
     let mut conn = pool.acquire().await?;
-    let (orders, pagination) = OrdersTable::query_compact()
+    let mut query = OrdersTable::query_compact();
+    if let Some(actor_id) = actor_id {
+        query.bind_actor_id(actor_id);
+    }
+
+    let (orders, pagination) = query
         .bind_statuses(statuses.as_ref().map_or(
             &[
                 OrderStatus::Completed,
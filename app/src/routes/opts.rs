@@ -7,14 +7,17 @@ use axum::{
 
 use graphein_common::{
     AppError, AppState, HandlerResponse,
-    database::{PapersTable, SettingsTable},
+    database::{BindingsTable, LaminationsTable, PapersTable, PricingTable, SettingsTable},
     error::{BadRequestError, ForbiddenError, NotFoundError},
     extract::Json,
     middleware::{merchant_only, requires_onboarding},
     response::ResponseBuilder,
     schemas::{
-        Paper, PaperCreate, PaperId, PaperUpdate, PaperVariant, PaperVariantCreate, PaperVariantId,
-        PaperWithoutVariants, Settings, SettingsUpdate,
+        Binding, BindingColour, BindingColourCreate, BindingColourId, BindingCreate, BindingId,
+        BindingPaper, BindingUpdate, BindingWithoutColours, LaminationFilm, LaminationFilmCreate,
+        LaminationFilmId, LaminationOptions, LaminationOptionsUpdate, Paper, PaperCreate, PaperId,
+        PaperUpdate, PaperVariant, PaperVariantCreate, PaperVariantId, PaperWithoutVariants,
+        PricingRates, Settings, SettingsUpdate,
     },
 };
 use http::StatusCode;
@@ -50,12 +53,38 @@ pub(super) fn expand_router(state: AppState) -> Router<AppState> {
                 .delete(delete_opts_papers_id_variants_id)
                 .route_layer(middleware::from_fn_with_state(state.clone(), merchant_only)),
         )
+        .route("/pricing", get(get_opts_pricing))
+        .route(
+            "/pricing",
+            put(put_opts_pricing)
+                .route_layer(middleware::from_fn_with_state(state.clone(), merchant_only)),
+        )
         .route("/services/binding", get(get_opts_services_binding))
         .route(
             "/services/binding",
             post(post_opts_services_binding)
-                .put(put_opts_services_binding)
-                .delete(delete_opts_services_binding)
+                .route_layer(middleware::from_fn_with_state(state.clone(), merchant_only)),
+        )
+        .route(
+            "/services/binding/{id}",
+            put(put_opts_services_binding_id)
+                .delete(delete_opts_services_binding_id)
+                .route_layer(middleware::from_fn_with_state(state.clone(), merchant_only)),
+        )
+        .route(
+            "/services/binding/{id}/colours",
+            post(post_opts_services_binding_id_colours)
+                .route_layer(middleware::from_fn_with_state(state.clone(), merchant_only)),
+        )
+        .route(
+            "/services/binding/{id}/colours/{id}",
+            put(put_opts_services_binding_id_colours_id)
+                .delete(delete_opts_services_binding_id_colours_id)
+                .route_layer(middleware::from_fn_with_state(state.clone(), merchant_only)),
+        )
+        .route(
+            "/services/binding/{id}/papers",
+            put(put_opts_services_binding_id_papers)
                 .route_layer(middleware::from_fn_with_state(state.clone(), merchant_only)),
         )
         .route("/services/laminate", get(get_opts_services_laminate))
@@ -64,6 +93,17 @@ pub(super) fn expand_router(state: AppState) -> Router<AppState> {
             post(post_opts_services_laminate)
                 .route_layer(middleware::from_fn_with_state(state.clone(), merchant_only)),
         )
+        .route(
+            "/services/laminate/films",
+            post(post_opts_services_laminate_films)
+                .route_layer(middleware::from_fn_with_state(state.clone(), merchant_only)),
+        )
+        .route(
+            "/services/laminate/films/{id}",
+            put(put_opts_services_laminate_films_id)
+                .delete(delete_opts_services_laminate_films_id)
+                .route_layer(middleware::from_fn_with_state(state.clone(), merchant_only)),
+        )
         .route_layer(middleware::from_fn_with_state(state, requires_onboarding))
 }
 
@@ -222,7 +262,6 @@ async fn post_opts_papers_id_variants(
             name: request_data.name,
             is_default: request_data.is_default,
             is_available: request_data.is_available,
-            is_laminatable: request_data.is_laminatable,
         })
         .build())
 }
@@ -251,7 +290,6 @@ async fn put_opts_papers_id_variants_id(
             name: request_data.name,
             is_default: request_data.is_default,
             is_available: request_data.is_available,
-            is_laminatable: request_data.is_laminatable,
         })
         .build())
 }
@@ -276,32 +314,341 @@ async fn delete_opts_papers_id_variants_id(
     Ok(StatusCode::NO_CONTENT)
 }
 
-// TODO: Unimplemented
-async fn get_opts_services_binding() -> HandlerResponse<()> {
-    Err(AppError::NotFound(NotFoundError::PathNotFound))
+async fn get_opts_pricing(
+    State(AppState { pool, .. }): State<AppState>,
+) -> HandlerResponse<PricingRates> {
+    let mut conn = pool.acquire().await?;
+    let pricing_rates = PricingTable::fetch_rates(&mut conn).await?;
+
+    Ok(ResponseBuilder::new().data(pricing_rates).build())
+}
+
+/// Maps a foreign key violation to the unknown paper variant which caused it.
+fn map_pricing_foreign_key_violation(source: sqlx::Error) -> AppError {
+    match &source {
+        sqlx::Error::Database(database_error) if database_error.is_foreign_key_violation() => {
+            AppError::BadRequest(BadRequestError::MalformedJson(
+                "Request data contains unknown paper variants".into(),
+            ))
+        }
+        _ => source.into(),
+    }
 }
 
-// TODO: Unimplemented
-async fn post_opts_services_binding() -> HandlerResponse<()> {
-    Err(AppError::NotFound(NotFoundError::PathNotFound))
+async fn put_opts_pricing(
+    State(AppState { pool, .. }): State<AppState>,
+    Json(request_data): Json<PricingRates>,
+) -> HandlerResponse<PricingRates> {
+    if request_data
+        .paper_variants
+        .iter()
+        .any(|rate| rate.sheet_rate < 0 || rate.mono_side_rate < 0 || rate.colour_side_rate < 0)
+        || request_data
+            .services
+            .iter()
+            .any(|rate| rate.base_rate < 0 || rate.sheet_rate < 0)
+    {
+        return Err(AppError::BadRequest(BadRequestError::MalformedJson(
+            "Request data contains negative rates".into(),
+        )));
+    }
+
+    let mut tx = pool.begin().await?;
+    PricingTable::upsert_rates(&mut tx, &request_data)
+        .await
+        .map_err(map_pricing_foreign_key_violation)?;
+    let pricing_rates = PricingTable::fetch_rates(&mut tx).await?;
+    tx.commit().await?;
+
+    Ok(ResponseBuilder::new().data(pricing_rates).build())
 }
 
-// TODO: Unimplemented
-async fn put_opts_services_binding() -> HandlerResponse<()> {
-    Err(AppError::NotFound(NotFoundError::PathNotFound))
+async fn get_opts_services_binding(
+    State(AppState { pool, .. }): State<AppState>,
+) -> HandlerResponse<Vec<Binding>> {
+    let mut conn = pool.acquire().await?;
+    let bindings = BindingsTable::fetch_all(&mut conn).await?;
+
+    Ok(ResponseBuilder::new().data(bindings).build())
+}
+
+async fn post_opts_services_binding(
+    State(AppState { pool, .. }): State<AppState>,
+    Json(request_data): Json<BindingCreate>,
+) -> HandlerResponse<Binding> {
+    if request_data.name.is_empty()
+        || request_data
+            .colours
+            .iter()
+            .any(|binding_colour| binding_colour.colour.is_empty())
+    {
+        return Err(AppError::BadRequest(BadRequestError::MalformedJson(
+            "Request data contains malformed data for name and/or colours".into(),
+        )));
+    }
+
+    let mut tx = pool.begin().await?;
+    let (binding_id, binding_colours) = BindingsTable::create_new(&mut tx, &request_data)
+        .await
+        .map_err(map_binding_foreign_key_violation)?;
+    tx.commit().await?;
+
+    Ok(ResponseBuilder::new()
+        .data(Binding {
+            id: binding_id,
+            name: request_data.name,
+            is_available: request_data.is_available,
+            colours: binding_colours,
+            papers: request_data.papers,
+        })
+        .build())
 }
 
-// TODO: Unimplemented
-async fn delete_opts_services_binding() -> HandlerResponse<()> {
-    Err(AppError::NotFound(NotFoundError::PathNotFound))
+async fn put_opts_services_binding_id(
+    State(AppState { pool, .. }): State<AppState>,
+    Path(binding_id): Path<BindingId>,
+    Json(request_data): Json<BindingUpdate>,
+) -> HandlerResponse<BindingWithoutColours> {
+    if request_data.name.is_empty() {
+        return Err(AppError::BadRequest(BadRequestError::MalformedJson(
+            "Request data contains malformed data for name".into(),
+        )));
+    }
+
+    let mut tx = pool.begin().await?;
+    BindingsTable::update(&mut tx, binding_id, &request_data).await?;
+    tx.commit().await?;
+
+    Ok(ResponseBuilder::new()
+        .data(BindingWithoutColours {
+            id: binding_id,
+            name: request_data.name,
+            is_available: request_data.is_available,
+        })
+        .build())
 }
 
-// TODO: Unimplemented
-async fn get_opts_services_laminate() -> HandlerResponse<()> {
-    Err(AppError::NotFound(NotFoundError::PathNotFound))
+async fn delete_opts_services_binding_id(
+    State(AppState { pool, .. }): State<AppState>,
+    Path(binding_id): Path<BindingId>,
+) -> Result<StatusCode, AppError> {
+    let mut tx = pool.begin().await?;
+    BindingsTable::delete(&mut tx, binding_id).await?;
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
 }
 
-// TODO: Unimplemented
-async fn post_opts_services_laminate() -> HandlerResponse<()> {
-    Err(AppError::NotFound(NotFoundError::PathNotFound))
+async fn post_opts_services_binding_id_colours(
+    State(AppState { pool, .. }): State<AppState>,
+    Path(binding_id): Path<BindingId>,
+    Json(request_data): Json<BindingColourCreate>,
+) -> HandlerResponse<BindingColour> {
+    if request_data.colour.is_empty() {
+        return Err(AppError::BadRequest(BadRequestError::MalformedJson(
+            "Request data contains malformed data for colour".into(),
+        )));
+    }
+
+    let mut tx = pool.begin().await?;
+    let binding_colour_id = BindingsTable::create_new_colour(&mut tx, binding_id, &request_data)
+        .await
+        .map_err(map_binding_colour_violation)?;
+    tx.commit().await?;
+
+    Ok(ResponseBuilder::new()
+        .data(BindingColour {
+            id: binding_colour_id,
+            colour: request_data.colour,
+            is_available: request_data.is_available,
+        })
+        .build())
+}
+
+async fn put_opts_services_binding_id_colours_id(
+    State(AppState { pool, .. }): State<AppState>,
+    Path((binding_id, binding_colour_id)): Path<(BindingId, BindingColourId)>,
+    Json(request_data): Json<BindingColourCreate>,
+) -> HandlerResponse<BindingColour> {
+    if request_data.colour.is_empty() {
+        return Err(AppError::BadRequest(BadRequestError::MalformedJson(
+            "Request data contains malformed data for colour".into(),
+        )));
+    }
+
+    let mut tx = pool.begin().await?;
+    BindingsTable::update_colour(&mut tx, binding_id, binding_colour_id, &request_data)
+        .await
+        .map_err(map_binding_colour_violation)?;
+    tx.commit().await?;
+
+    Ok(ResponseBuilder::new()
+        .data(BindingColour {
+            id: binding_colour_id,
+            colour: request_data.colour,
+            is_available: request_data.is_available,
+        })
+        .build())
+}
+
+async fn delete_opts_services_binding_id_colours_id(
+    State(AppState { pool, .. }): State<AppState>,
+    Path((binding_id, binding_colour_id)): Path<(BindingId, BindingColourId)>,
+) -> Result<StatusCode, AppError> {
+    let mut tx = pool.begin().await?;
+    BindingsTable::delete_colour(&mut tx, binding_id, binding_colour_id).await?;
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn put_opts_services_binding_id_papers(
+    State(AppState { pool, .. }): State<AppState>,
+    Path(binding_id): Path<BindingId>,
+    Json(request_data): Json<Vec<BindingPaper>>,
+) -> HandlerResponse<Vec<BindingPaper>> {
+    let mut tx = pool.begin().await?;
+    BindingsTable::set_papers(&mut tx, binding_id, &request_data)
+        .await
+        .map_err(map_binding_foreign_key_violation)?;
+    tx.commit().await?;
+
+    Ok(ResponseBuilder::new().data(request_data).build())
+}
+
+/// Maps a foreign key violation to the missing binding or the unknown paper which caused it.
+fn map_binding_foreign_key_violation(source: sqlx::Error) -> AppError {
+    match &source {
+        sqlx::Error::Database(database_error) if database_error.is_foreign_key_violation() => {
+            if database_error.constraint() == Some("bindings_papers_paper_id_fkey") {
+                AppError::BadRequest(BadRequestError::MalformedJson(
+                    "Request data contains unknown papers".into(),
+                ))
+            } else {
+                AppError::NotFound(NotFoundError::ResourceNotFound)
+            }
+        }
+        _ => source.into(),
+    }
+}
+
+/// Maps a unique violation to the duplicate colour which caused it, falling back to the foreign
+/// key violations of the binding.
+fn map_binding_colour_violation(source: sqlx::Error) -> AppError {
+    match &source {
+        sqlx::Error::Database(database_error) if database_error.is_unique_violation() => {
+            AppError::BadRequest(BadRequestError::MalformedJson(
+                "Request data contains a colour which the binding already has".into(),
+            ))
+        }
+        _ => map_binding_foreign_key_violation(source),
+    }
+}
+
+async fn get_opts_services_laminate(
+    State(AppState { pool, .. }): State<AppState>,
+) -> HandlerResponse<LaminationOptions> {
+    let mut conn = pool.acquire().await?;
+    let lamination_options = LaminationOptions {
+        is_serviceable: LaminationsTable::fetch_is_serviceable(&mut conn).await?,
+        films: LaminationsTable::fetch_films(&mut conn).await?,
+        paper_variants: LaminationsTable::fetch_paper_variants(&mut conn).await?,
+    };
+
+    Ok(ResponseBuilder::new().data(lamination_options).build())
+}
+
+async fn post_opts_services_laminate(
+    State(AppState { pool, .. }): State<AppState>,
+    Json(request_data): Json<LaminationOptionsUpdate>,
+) -> HandlerResponse<LaminationOptions> {
+    let mut tx = pool.begin().await?;
+    LaminationsTable::set_is_serviceable(&mut tx, request_data.is_serviceable).await?;
+    LaminationsTable::set_laminatable_paper_variants(
+        &mut tx,
+        &request_data.laminatable_paper_variant_ids,
+    )
+    .await?;
+    let lamination_options = LaminationOptions {
+        is_serviceable: request_data.is_serviceable,
+        films: LaminationsTable::fetch_films(&mut tx).await?,
+        paper_variants: LaminationsTable::fetch_paper_variants(&mut tx).await?,
+    };
+    tx.commit().await?;
+
+    Ok(ResponseBuilder::new().data(lamination_options).build())
+}
+
+async fn post_opts_services_laminate_films(
+    State(AppState { pool, .. }): State<AppState>,
+    Json(request_data): Json<LaminationFilmCreate>,
+) -> HandlerResponse<LaminationFilm> {
+    if request_data.name.is_empty() {
+        return Err(AppError::BadRequest(BadRequestError::MalformedJson(
+            "Request data contains malformed data for name".into(),
+        )));
+    }
+
+    let mut tx = pool.begin().await?;
+    let film_id = LaminationsTable::create_new_film(&mut tx, &request_data)
+        .await
+        .map_err(map_lamination_film_unique_violation)?;
+    tx.commit().await?;
+
+    Ok(ResponseBuilder::new()
+        .data(LaminationFilm {
+            id: film_id,
+            name: request_data.name,
+            is_available: request_data.is_available,
+        })
+        .build())
+}
+
+async fn put_opts_services_laminate_films_id(
+    State(AppState { pool, .. }): State<AppState>,
+    Path(film_id): Path<LaminationFilmId>,
+    Json(request_data): Json<LaminationFilmCreate>,
+) -> HandlerResponse<LaminationFilm> {
+    if request_data.name.is_empty() {
+        return Err(AppError::BadRequest(BadRequestError::MalformedJson(
+            "Request data contains malformed data for name".into(),
+        )));
+    }
+
+    let mut tx = pool.begin().await?;
+    LaminationsTable::update_film(&mut tx, film_id, &request_data)
+        .await
+        .map_err(map_lamination_film_unique_violation)?;
+    tx.commit().await?;
+
+    Ok(ResponseBuilder::new()
+        .data(LaminationFilm {
+            id: film_id,
+            name: request_data.name,
+            is_available: request_data.is_available,
+        })
+        .build())
+}
+
+async fn delete_opts_services_laminate_films_id(
+    State(AppState { pool, .. }): State<AppState>,
+    Path(film_id): Path<LaminationFilmId>,
+) -> Result<StatusCode, AppError> {
+    let mut tx = pool.begin().await?;
+    LaminationsTable::delete_film(&mut tx, film_id).await?;
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Maps a unique violation to the duplicate name which caused it.
+fn map_lamination_film_unique_violation(source: sqlx::Error) -> AppError {
+    match &source {
+        sqlx::Error::Database(database_error) if database_error.is_unique_violation() => {
+            AppError::BadRequest(BadRequestError::MalformedJson(
+                "Request data contains a name which another lamination film already has".into(),
+            ))
+        }
+        _ => source.into(),
+    }
 }
@@ -5,23 +5,24 @@ use axum::{
     extract::State,
     middleware,
     response::{IntoResponse, Response},
-    routing::{delete, get, post},
+    routing::{delete, get, patch, post},
 };
 use futures::stream::{StreamExt as _, TryStreamExt as _};
 
 use graphein_common::{
-    AppError, AppState, HandlerResponse, MAX_FILE_LIMIT,
+    AppError, AppState, HandlerResponse, MAX_FILE_LIMIT, ThumbnailJob,
     auth::Session,
-    database::{FilesTable, OrdersTable},
+    database::{FilesTable, OrderEditor, OrdersTable},
     dto::RequestData,
     error::{BadRequestError, NotFoundError},
     extract::{Json, Path, QsQuery},
     middleware::{client_only, is_accepting_only, merchant_only, requires_onboarding},
     response::ResponseBuilder,
     schemas::{
-        ClientOrdersGlance, CompactOrder, DetailedOrder, FileId, FilePresignResponse,
-        FileUploadCreate, FileUploadResponse, OrderCreate, OrderId, OrderPriceUpdate,
-        OrderStatusUpdate,
+        ClientOrdersGlance, CompactOrder, DetailedOrder, FileId, FilePagePreview,
+        FilePresignResponse, FileUploadCreate, FileUploadResponse, MultipartUpload, OrderCreate,
+        OrderId, OrderStatusReason, OrderStatusSet, OrderStatusUpdate, OrderStatusUpdateCreate,
+        OrderUpdate, ThumbnailJobStatus,
         enums::{FileType, OrderStatus, UserRole},
     },
 };
@@ -52,16 +53,25 @@ pub(super) fn expand_router(state: AppState) -> Router<AppState> {
         .route("/{id}", get(get_orders_id))
         .route(
             "/{id}",
-            delete(delete_orders_id)
+            patch(patch_orders_id)
                 .route_layer(middleware::from_fn_with_state(state.clone(), client_only))
                 .route_layer(middleware::from_fn_with_state(
                     state.clone(),
                     is_accepting_only,
                 )),
         )
+        .route(
+            "/{id}",
+            // Clients cancel their orders and merchants reject them, with a reason
+            delete(delete_orders_id).route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                is_accepting_only,
+            )),
+        )
         .route(
             "/{id}/status",
             post(post_orders_id_status)
+                .put(put_orders_id_status)
                 .route_layer(middleware::from_fn_with_state(state.clone(), merchant_only)),
         )
         .route(
@@ -87,10 +97,21 @@ pub(super) fn expand_router(state: AppState) -> Router<AppState> {
                     is_accepting_only,
                 )),
         )
+        .route(
+            "/{id}/files/{id}/upload",
+            get(get_orders_id_files_id_upload)
+                .post(post_orders_id_files_id_upload)
+                .route_layer(middleware::from_fn_with_state(state.clone(), client_only))
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    is_accepting_only,
+                )),
+        )
         .route(
             "/{id}/files/{id}/thumbnail",
             get(get_orders_id_files_id_thumbnail),
         )
+        .route("/{id}/files/{id}/pages", get(get_orders_id_files_id_pages))
         .route(
             "/{id}/files/{id}",
             delete(delete_orders_id_files_id)
@@ -179,6 +200,7 @@ async fn get_orders_id(
 
     let order = OrdersTable::query_detailed(id)
         .with_owner(matches!(session.user_role, UserRole::Merchant))
+        .with_actors(matches!(session.user_role, UserRole::Merchant))
         .fetch_one(&mut conn)
         .await?;
 
@@ -190,8 +212,12 @@ async fn post_orders_id_status(
     State(AppState { pool, .. }): State<AppState>,
     session: Session,
     Path(order_id): Path<OrderId>,
-    request_data: Option<Json<OrderPriceUpdate>>,
+    request_data: Option<Json<OrderStatusUpdateCreate>>,
 ) -> HandlerResponse<OrderStatusUpdate> {
+    let OrderStatusUpdateCreate { price, reason } = request_data
+        .map(|Json(request_data)| request_data)
+        .unwrap_or_default();
+
     OrdersTable::permissions_checker(order_id, session)
         .allow_merchant(true)
         .test(&mut *(pool.acquire().await?))
@@ -199,20 +225,26 @@ async fn post_orders_id_status(
 
     let mut tx = pool.begin().await?;
     let previous_status = OrdersTable::fetch_status_for_update(&mut tx, order_id).await?;
-    let next_status = match previous_status {
-        OrderStatus::Reviewing => OrderStatus::Processing,
-        OrderStatus::Processing => OrderStatus::Ready,
-        OrderStatus::Ready => OrderStatus::Completed,
-        _ => {
-            return Err(AppError::BadRequest(
-                BadRequestError::UnprocessableStatusUpdate,
-            ));
-        }
-    };
-    let order_status_update = OrdersTable::update_status(&mut tx, order_id, next_status).await?;
+    let next_status = previous_status
+        .next()
+        .filter(|next_status| {
+            previous_status.can_transition_to(*next_status, Some(session.user_role))
+        })
+        .ok_or(AppError::BadRequest(
+            BadRequestError::UnprocessableStatusUpdate,
+        ))?;
+    let order_status_update = OrdersTable::update_status(
+        &mut tx,
+        order_id,
+        next_status,
+        &reason.normalise(),
+        session.user_id,
+    )
+    .await?;
     if matches!(next_status, OrderStatus::Processing) {
-        if let Some(Json(OrderPriceUpdate { price })) = request_data {
-            OrdersTable::update_price(&mut tx, order_id, price).await?;
+        match price {
+            Some(price) => OrdersTable::update_price(&mut tx, order_id, price).await?,
+            None => OrdersTable::accept_quote(&mut tx, order_id).await?,
         }
     }
     tx.commit().await?;
@@ -220,10 +252,45 @@ async fn post_orders_id_status(
     Ok(ResponseBuilder::new().data(order_status_update).build())
 }
 
+async fn put_orders_id_status(
+    State(AppState { pool, .. }): State<AppState>,
+    session: Session,
+    Path(order_id): Path<OrderId>,
+    Json(OrderStatusSet { status, reason }): Json<OrderStatusSet>,
+) -> HandlerResponse<OrderStatusUpdate> {
+    let reason = reason.normalise();
+    if reason.reason.is_none() {
+        return Err(AppError::BadRequest(BadRequestError::MalformedJson(
+            "A reason is required to set the status of an order".into(),
+        )));
+    }
+
+    let mut tx = pool.begin().await?;
+    let previous_status = OrdersTable::fetch_status_for_update(&mut tx, order_id).await?;
+    if !previous_status.can_transition_to(status, Some(session.user_role)) {
+        return Err(AppError::BadRequest(
+            BadRequestError::UnprocessableStatusUpdate,
+        ));
+    }
+
+    let order_status_update =
+        OrdersTable::update_status(&mut tx, order_id, status, &reason, session.user_id).await?;
+    if matches!(
+        (previous_status, status),
+        (OrderStatus::Reviewing, OrderStatus::Processing)
+    ) {
+        OrdersTable::accept_quote(&mut tx, order_id).await?;
+    }
+    tx.commit().await?;
+
+    Ok(ResponseBuilder::new().data(order_status_update).build())
+}
+
 async fn post_orders_id_build(
     State(AppState {
         pool,
         bucket,
+        converter,
         draft_orders,
         ..
     }): State<AppState>,
@@ -234,7 +301,7 @@ async fn post_orders_id_build(
     draft_orders.exists(user_id, order_id).await?;
     if request_data.files.is_empty() {
         return Err(AppError::BadRequest(BadRequestError::MalformedFiles(
-            "There are no files present in this order",
+            "There are no files present in this order".into(),
         )));
     }
 
@@ -249,7 +316,9 @@ async fn post_orders_id_build(
         }
     });
 
-    let order = draft_orders.build(&bucket, user_id, request_data).await?;
+    let order = draft_orders
+        .build(&bucket, &converter, &pool, user_id, request_data)
+        .await?;
     let mut tx = pool.begin().await?;
     OrdersTable::create_new(&mut tx, &order).await?;
     tx.commit().await?;
@@ -266,26 +335,69 @@ async fn delete_orders_id(
     }): State<AppState>,
     session: Session,
     Path(order_id): Path<OrderId>,
+    request_data: Option<Json<OrderStatusReason>>,
 ) -> Result<StatusCode, AppError> {
     if draft_orders.delete(session.user_id).await {
         return Ok(StatusCode::NO_CONTENT);
     }
 
-    let mut conn = pool.acquire().await?;
     OrdersTable::permissions_checker(order_id, session)
         .allow_merchant(true)
-        .test(&mut conn)
+        .test(&mut *(pool.acquire().await?))
         .await?;
 
     let cancelled_or_rejected = match session.user_role {
         UserRole::Student | UserRole::Teacher => OrderStatus::Cancelled,
         UserRole::Merchant => OrderStatus::Rejected,
     };
-    OrdersTable::update_status(&mut conn, order_id, cancelled_or_rejected).await?;
+    let reason = request_data
+        .map(|Json(reason)| reason)
+        .unwrap_or_default()
+        .normalise();
+    if matches!(cancelled_or_rejected, OrderStatus::Rejected) && reason.reason.is_none() {
+        return Err(AppError::BadRequest(BadRequestError::MalformedJson(
+            "A reason is required to reject an order".into(),
+        )));
+    }
+
+    let mut tx = pool.begin().await?;
+    let previous_status = OrdersTable::fetch_status_for_update(&mut tx, order_id).await?;
+    if !previous_status.can_transition_to(cancelled_or_rejected, Some(session.user_role)) {
+        return Err(AppError::BadRequest(
+            BadRequestError::UnprocessableStatusUpdate,
+        ));
+    }
+
+    OrdersTable::update_status(
+        &mut tx,
+        order_id,
+        cancelled_or_rejected,
+        &reason,
+        session.user_id,
+    )
+    .await?;
+    tx.commit().await?;
 
     Ok(StatusCode::NO_CONTENT)
 }
 
+async fn patch_orders_id(
+    State(AppState { pool, .. }): State<AppState>,
+    session: Session,
+    Path(order_id): Path<OrderId>,
+    Json(request_data): Json<OrderUpdate>,
+) -> HandlerResponse<DetailedOrder> {
+    OrdersTable::permissions_checker(order_id, session)
+        .test(&mut *(pool.acquire().await?))
+        .await?;
+
+    let mut tx = pool.begin().await?;
+    let order = OrderEditor::edit(&mut tx, order_id, session.user_id, request_data).await?;
+    tx.commit().await?;
+
+    Ok(ResponseBuilder::new().data(order).build())
+}
+
 async fn get_orders_id_files(
     State(AppState { pool, bucket, .. }): State<AppState>,
     session: Session,
@@ -305,11 +417,15 @@ async fn get_orders_id_files(
             Ok(tx
                 .send(FilePresignResponse {
                     id: meta.id,
-                    url: bucket.presign_get_file(
-                        &meta.object_key,
-                        &meta.filename,
-                        meta.filetype,
-                    )?,
+                    url: if meta.is_purged {
+                        None
+                    } else {
+                        Some(bucket.presign_get_file(
+                            &meta.object_key,
+                            &meta.filename,
+                            meta.filetype,
+                        )?)
+                    },
                 })
                 .await?)
         })
@@ -330,28 +446,98 @@ async fn post_orders_id_files(
     }): State<AppState>,
     Session { user_id, .. }: Session,
     Path(order_id): Path<OrderId>,
-    Json(FileUploadCreate { filetype, filesize }): Json<FileUploadCreate>,
+    Json(FileUploadCreate {
+        filetype,
+        filesize,
+        multipart,
+    }): Json<FileUploadCreate>,
 ) -> HandlerResponse<FileUploadResponse> {
     draft_orders.exists(user_id, order_id).await?;
 
     let (file_id, object_key) = draft_orders.add_file(user_id, filetype, filesize).await?;
-    let upload_url = bucket.presign_put(
-        &draft_orders.get_created_at(user_id).await?,
-        filetype,
-        filesize,
-        &object_key,
-    )?;
+    let (upload_url, multipart) = if multipart {
+        let upload_id = match bucket
+            .create_multipart_upload(&object_key, filetype, filesize)
+            .await
+        {
+            Ok(upload_id) => upload_id,
+            Err(err) => {
+                draft_orders.remove_file(user_id, file_id).await?;
+                return Err(err);
+            }
+        };
+        draft_orders
+            .set_upload_id(user_id, file_id, Some(upload_id.clone()))
+            .await?;
+
+        (
+            None,
+            Some(
+                bucket
+                    .presign_multipart_upload(&object_key, filetype, filesize, &upload_id)
+                    .await?,
+            ),
+        )
+    } else {
+        let upload_url = bucket.presign_put(filetype, filesize, &object_key)?;
+
+        (Some(upload_url), None)
+    };
 
     Ok(ResponseBuilder::new()
         .data(FileUploadResponse {
             id: file_id,
             object_key,
             upload_url,
+            multipart,
         })
         .status_code(StatusCode::ACCEPTED)
         .build())
 }
 
+async fn get_orders_id_files_id_upload(
+    State(AppState {
+        bucket,
+        draft_orders,
+        ..
+    }): State<AppState>,
+    Session { user_id, .. }: Session,
+    Path((order_id, file_id)): Path<(OrderId, FileId)>,
+) -> HandlerResponse<MultipartUpload> {
+    draft_orders.exists(user_id, order_id).await?;
+    let (object_key, filetype, filesize, upload_id) =
+        draft_orders.get_upload(user_id, file_id).await?;
+    draft_orders.touch(user_id).await?;
+
+    Ok(ResponseBuilder::new()
+        .data(
+            bucket
+                .presign_multipart_upload(&object_key, filetype, filesize, &upload_id)
+                .await?,
+        )
+        .build())
+}
+
+async fn post_orders_id_files_id_upload(
+    State(AppState {
+        bucket,
+        draft_orders,
+        ..
+    }): State<AppState>,
+    Session { user_id, .. }: Session,
+    Path((order_id, file_id)): Path<(OrderId, FileId)>,
+) -> Result<StatusCode, AppError> {
+    draft_orders.exists(user_id, order_id).await?;
+    let (object_key, filetype, filesize, upload_id) =
+        draft_orders.get_upload(user_id, file_id).await?;
+    bucket
+        .complete_multipart_upload(&object_key, filetype, filesize, &upload_id)
+        .await?;
+    draft_orders.set_upload_id(user_id, file_id, None).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
 async fn get_orders_id_files_id_thumbnail(
     State(AppState {
         pool,
@@ -381,6 +567,9 @@ async fn get_orders_id_files_id_thumbnail(
 
         let file =
             FilesTable::fetch_one_for_metadata_from_order(&mut conn, order_id, file_id).await?;
+        if file.is_purged {
+            return Err(AppError::NotFound(NotFoundError::FilePurged));
+        }
 
         (file.object_key, file.filetype)
     };
@@ -389,11 +578,27 @@ async fn get_orders_id_files_id_thumbnail(
         .presign_get_file_thumbnail(&object_key, filetype)
         .await?
     else {
-        thumbnailer
-            .signal_for_processing(object_key, filetype)
-            .await?;
-
-        return Ok(StatusCode::ACCEPTED.into_response());
+        // Files in draft orders are only converted once their order is placed
+        let job_status = if !filetype.is_thumbnailable()
+            && bucket
+                .exists(&object_key, filetype.printable())
+                .await
+                .is_err()
+        {
+            ThumbnailJobStatus::AwaitingConversion
+        } else {
+            // A job without pages only has the thumbnail of the file
+            thumbnailer
+                .signal_for_processing(ThumbnailJob::new(object_key, filetype))
+                .await?
+                .swap_remove(0)
+        };
+
+        return Ok(ResponseBuilder::new()
+            .data(job_status)
+            .status_code(StatusCode::ACCEPTED)
+            .build()
+            .into_response());
     };
 
     Ok(ResponseBuilder::new()
@@ -402,6 +607,67 @@ async fn get_orders_id_files_id_thumbnail(
         .into_response())
 }
 
+async fn get_orders_id_files_id_pages(
+    State(AppState {
+        config,
+        pool,
+        bucket,
+        thumbnailer,
+        ..
+    }): State<AppState>,
+    session: Session,
+    Path((order_id, file_id)): Path<(OrderId, FileId)>,
+) -> HandlerResponse<Vec<FilePagePreview>> {
+    let mut conn = pool.acquire().await?;
+    OrdersTable::permissions_checker(order_id, session)
+        .allow_merchant(true)
+        .test(&mut conn)
+        .await?;
+
+    let file = FilesTable::fetch_one_for_metadata_from_order(&mut conn, order_id, file_id).await?;
+    if file.is_purged {
+        return Err(AppError::NotFound(NotFoundError::FilePurged));
+    }
+
+    let pages = match file.filetype.printable() {
+        FileType::Pdf => file
+            .page_count
+            .and_then(|page_count| u32::try_from(page_count).ok())
+            .unwrap_or(1)
+            .min(config.preview_page_limit()),
+        _ => 1,
+    };
+
+    let urls = bucket
+        .presign_get_file_pages(&file.object_key, file.filetype, pages)
+        .await?;
+    let missing_pages = (1..=pages)
+        .zip(&urls)
+        .filter_map(|(page, url)| url.is_none().then_some(page))
+        .collect::<Vec<_>>();
+    let mut jobs = if missing_pages.is_empty() {
+        Vec::new()
+    } else {
+        thumbnailer
+            .signal_for_processing(
+                ThumbnailJob::new(file.object_key, file.filetype).with_pages(missing_pages),
+            )
+            .await?
+    }
+    .into_iter();
+
+    let page_previews = (1..=pages)
+        .zip(urls)
+        .map(|(page, url)| {
+            let job = if url.is_none() { jobs.next() } else { None };
+
+            FilePagePreview { page, url, job }
+        })
+        .collect();
+
+    Ok(ResponseBuilder::new().data(page_previews).build())
+}
+
 async fn delete_orders_id_files_id(
     State(AppState {
         bucket,
@@ -419,6 +685,11 @@ async fn delete_orders_id_files_id(
         .find(|file| file.id == file_id)
         .ok_or(NotFoundError::ResourceNotFound)?;
 
+    if let Some(upload_id) = &draft_file.upload_id {
+        bucket
+            .abort_multipart_upload(&draft_file.object_key, draft_file.filetype, upload_id)
+            .await?;
+    }
     bucket
         .delete_file(&draft_file.object_key, draft_file.filetype)
         .await?;
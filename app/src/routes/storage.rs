@@ -0,0 +1,132 @@
+use axum::{
+    Router,
+    body::Bytes,
+    extract::{DefaultBodyLimit, RawQuery, State},
+    response::IntoResponse,
+    routing::get,
+};
+use chrono::DateTime;
+use http::{
+    HeaderMap, StatusCode,
+    header::{CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_TYPE, EXPIRES},
+};
+use serde::Deserialize;
+
+use graphein_common::{
+    AppError, AppState, LocalStore, MAX_FILE_SIZE, ObjectStore as _,
+    error::{BadRequestError, NotFoundError},
+    extract::{Path, QsQuery},
+};
+
+/// The parameters signed by `LocalStore::presign_get`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct SignedGetParams {
+    expires: i64,
+    cache_control: String,
+    content_disposition: String,
+    content_type: String,
+}
+
+/// The parameters signed by `LocalStore::presign_put`, or by `LocalStore::presign_upload_part`
+/// for a part of a multipart upload.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct SignedPutParams {
+    expires: i64,
+    content_type: Option<String>,
+    content_length: Option<u64>,
+    upload_id: Option<String>,
+    part_number: Option<u16>,
+}
+
+#[allow(clippy::cast_possible_truncation)]
+pub(super) fn expand_router() -> Router<AppState> {
+    Router::new().route(
+        "/{object}",
+        get(get_storage_object)
+            .put(put_storage_object)
+            .layer(DefaultBodyLimit::max(MAX_FILE_SIZE as usize)),
+    )
+}
+
+fn local_store(state: &AppState) -> Result<&LocalStore, AppError> {
+    state
+        .bucket
+        .local_store()
+        .ok_or(AppError::NotFound(NotFoundError::PathNotFound))
+}
+
+async fn get_storage_object(
+    State(state): State<AppState>,
+    Path(object): Path<String>,
+    RawQuery(query): RawQuery,
+    QsQuery(params): QsQuery<SignedGetParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let store = local_store(&state)?;
+    store.verify(
+        "GET",
+        &object,
+        query.as_deref().unwrap_or(""),
+        params.expires,
+    )?;
+    let expires = DateTime::from_timestamp(params.expires, 0)
+        .unwrap_or_default()
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string();
+
+    Ok((
+        [
+            (CACHE_CONTROL, params.cache_control),
+            (CONTENT_DISPOSITION, params.content_disposition),
+            (CONTENT_TYPE, params.content_type),
+            (EXPIRES, expires),
+        ],
+        store.get(&object).await?,
+    ))
+}
+
+async fn put_storage_object(
+    State(state): State<AppState>,
+    Path(object): Path<String>,
+    RawQuery(query): RawQuery,
+    QsQuery(params): QsQuery<SignedPutParams>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<StatusCode, AppError> {
+    let store = local_store(&state)?;
+    store.verify(
+        "PUT",
+        &object,
+        query.as_deref().unwrap_or(""),
+        params.expires,
+    )?;
+
+    if let (Some(upload_id), Some(part_number)) = (params.upload_id, params.part_number) {
+        store
+            .put_part(&object, &upload_id, part_number, body)
+            .await?;
+
+        return Ok(StatusCode::OK);
+    }
+
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok());
+    if content_type.is_none() || content_type != params.content_type.as_deref() {
+        return Err(AppError::BadRequest(BadRequestError::MalformedFiles(
+            "File type does not match the upload URL.".into(),
+        )));
+    }
+    if Some(body.len() as u64) != params.content_length {
+        return Err(AppError::BadRequest(BadRequestError::MalformedFiles(
+            "File size does not match the upload URL.".into(),
+        )));
+    }
+
+    store
+        .put(&object, content_type.unwrap_or_default(), body)
+        .await?;
+
+    Ok(StatusCode::OK)
+}
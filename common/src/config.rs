@@ -1,14 +1,15 @@
 use std::{
+    env::VarError,
     net::IpAddr,
-    num::{NonZeroI32, NonZeroU32},
+    num::{NonZeroI32, NonZeroU32, NonZeroUsize},
+    path::PathBuf,
     str::FromStr,
     sync::Arc,
     time::Duration as StdDuration,
 };
 
-use anyhow::{Context as _, Result as AnyhowResult};
+use anyhow::{Context as _, Result as AnyhowResult, bail};
 use chrono::FixedOffset;
-use dotenvy::var;
 use sqlx::postgres::PgConnectOptions;
 
 #[derive(Debug)]
@@ -24,12 +25,30 @@ pub struct Config {
     session_expiry_time: StdDuration,
     shop_utc_offset: FixedOffset,
     thumbnail_size: NonZeroU32,
+    preview_page_limit: NonZeroU32,
+    thumbnailer_workers: NonZeroUsize,
+    thumbnailer_queue_size: NonZeroUsize,
+    office_converter: String,
+    orphan_grace_period: StdDuration,
+    orphan_collector_dry_run: bool,
     google_oauth_client_id: String,
     google_oauth_client_secret: String,
-    r2_account_id: String,
-    r2_bucket_name: String,
-    r2_access_key_id: String,
-    r2_secret_access_key: String,
+    storage: StorageConfig,
+}
+
+#[derive(Debug)]
+pub enum StorageConfig {
+    S3 {
+        endpoint: String,
+        path_style: bool,
+        region: String,
+        bucket_name: String,
+        access_key_id: String,
+        secret_access_key: String,
+    },
+    Local {
+        directory: PathBuf,
+    },
 }
 
 impl Config {
@@ -70,18 +89,34 @@ impl Config {
             .context("Invalid value for environment variable `THUMBNAIL_SIZE`")?
             .try_into()
             .context("Invalid value for environment variable `THUMBNAIL_SIZE`")?;
+        let preview_page_limit = var("PREVIEW_PAGE_LIMIT")
+            .unwrap_or(String::from("10"))
+            .parse()
+            .context("Invalid value for environment variable `PREVIEW_PAGE_LIMIT`")?;
+        let thumbnailer_workers = var("THUMBNAILER_WORKERS")
+            .unwrap_or(String::from("2"))
+            .parse()
+            .context("Invalid value for environment variable `THUMBNAILER_WORKERS`")?;
+        let thumbnailer_queue_size = var("THUMBNAILER_QUEUE_SIZE")
+            .unwrap_or(String::from("64"))
+            .parse()
+            .context("Invalid value for environment variable `THUMBNAILER_QUEUE_SIZE`")?;
+        let office_converter = var("OFFICE_CONVERTER").unwrap_or(String::from("soffice"));
+        let orphan_grace_period = StdDuration::from_secs(
+            var("ORPHAN_GRACE_PERIOD")
+                .unwrap_or(String::from("86400")) // 1 Day
+                .parse()
+                .context("Invalid value for environment variable `ORPHAN_GRACE_PERIOD`")?,
+        );
+        let orphan_collector_dry_run = var("ORPHAN_COLLECTOR_DRY_RUN")
+            .unwrap_or(String::from("false"))
+            .parse()
+            .context("Invalid value for environment variable `ORPHAN_COLLECTOR_DRY_RUN`")?;
         let google_oauth_client_id = var("GOOGLE_OAUTH_CLIENT_ID")
             .context("Missing environment variable `GOOGLE_OAUTH_CLIENT_ID`")?;
         let google_oauth_client_secret = var("GOOGLE_OAUTH_CLIENT_SECRET")
             .context("Missing environment variable `GOOGLE_OAUTH_CLIENT_SECRET`")?;
-        let r2_account_id =
-            var("R2_ACCOUNT_ID").context("Missing environment variable `R2_ACCOUNT_ID")?;
-        let r2_bucket_name =
-            var("R2_BUCKET_NAME").context("Missing environment variable `R2_BUCKET_NAME")?;
-        let r2_access_key_id =
-            var("R2_ACCESS_KEY_ID").context("Missing environment variable `R2_ACCESS_KEY_ID")?;
-        let r2_secret_access_key = var("R2_SECRET_ACCESS_KEY")
-            .context("Missing environment variable `R2_SECRET_ACCESS_KEY")?;
+        let storage = StorageConfig::try_from_dotenv()?;
 
         Ok(Arc::new(Config {
             host,
@@ -95,12 +130,15 @@ impl Config {
             session_expiry_time,
             shop_utc_offset,
             thumbnail_size,
+            preview_page_limit,
+            thumbnailer_workers,
+            thumbnailer_queue_size,
+            office_converter,
+            orphan_grace_period,
+            orphan_collector_dry_run,
             google_oauth_client_id,
             google_oauth_client_secret,
-            r2_account_id,
-            r2_bucket_name,
-            r2_access_key_id,
-            r2_secret_access_key,
+            storage,
         }))
     }
 
@@ -162,32 +200,110 @@ impl Config {
     }
 
     #[must_use]
-    pub fn google_oauth_client_id(&self) -> &str {
-        &self.google_oauth_client_id
+    pub fn preview_page_limit(&self) -> u32 {
+        self.preview_page_limit.get()
     }
 
     #[must_use]
-    pub fn google_oauth_client_secret(&self) -> &str {
-        &self.google_oauth_client_secret
+    pub fn thumbnailer_workers(&self) -> usize {
+        self.thumbnailer_workers.get()
+    }
+
+    #[must_use]
+    pub fn thumbnailer_queue_size(&self) -> usize {
+        self.thumbnailer_queue_size.get()
+    }
+
+    #[must_use]
+    pub fn office_converter(&self) -> &str {
+        &self.office_converter
     }
 
     #[must_use]
-    pub fn r2_account_id(&self) -> &str {
-        &self.r2_account_id
+    pub fn orphan_grace_period(&self) -> StdDuration {
+        self.orphan_grace_period
     }
 
     #[must_use]
-    pub fn r2_bucket_name(&self) -> &str {
-        &self.r2_bucket_name
+    pub fn orphan_collector_dry_run(&self) -> bool {
+        self.orphan_collector_dry_run
     }
 
     #[must_use]
-    pub fn r2_access_key_id(&self) -> &str {
-        &self.r2_access_key_id
+    pub fn google_oauth_client_id(&self) -> &str {
+        &self.google_oauth_client_id
+    }
+
+    #[must_use]
+    pub fn google_oauth_client_secret(&self) -> &str {
+        &self.google_oauth_client_secret
     }
 
     #[must_use]
-    pub fn r2_secret_access_key(&self) -> &str {
-        &self.r2_secret_access_key
+    pub fn storage(&self) -> &StorageConfig {
+        &self.storage
     }
 }
+
+impl StorageConfig {
+    fn try_from_dotenv() -> AnyhowResult<Self> {
+        match var("STORAGE_BACKEND")
+            .unwrap_or(String::from("s3"))
+            .to_lowercase()
+            .as_str()
+        {
+            "s3" => {
+                // Cloudflare R2 is assumed when no endpoint is given
+                let endpoint = match var("S3_ENDPOINT") {
+                    Ok(endpoint) => endpoint,
+                    Err(_) => format!(
+                        "https://{}.r2.cloudflarestorage.com",
+                        var("R2_ACCOUNT_ID")
+                            .context("Missing environment variable `R2_ACCOUNT_ID`")?,
+                    ),
+                };
+                let path_style = var("S3_PATH_STYLE")
+                    .unwrap_or(String::from("false"))
+                    .parse()
+                    .context("Invalid value for environment variable `S3_PATH_STYLE`")?;
+                let region = var("S3_REGION").unwrap_or(String::from("auto"));
+                let bucket_name = s3_var("BUCKET_NAME")?;
+                let access_key_id = s3_var("ACCESS_KEY_ID")?;
+                let secret_access_key = s3_var("SECRET_ACCESS_KEY")?;
+
+                Ok(Self::S3 {
+                    endpoint,
+                    path_style,
+                    region,
+                    bucket_name,
+                    access_key_id,
+                    secret_access_key,
+                })
+            }
+            "local" => Ok(Self::Local {
+                directory: PathBuf::from(
+                    var("STORAGE_DIRECTORY").unwrap_or(String::from("storage")),
+                ),
+            }),
+            _ => bail!("Invalid value for environment variable `STORAGE_BACKEND`"),
+        }
+    }
+}
+
+/// Reads an environment variable, treating an empty value as unset so that defaults still apply
+/// to variables which are passed through empty, as Docker Compose does for unset variables.
+fn var(key: &str) -> Result<String, dotenvy::Error> {
+    match dotenvy::var(key)? {
+        value if value.is_empty() => Err(dotenvy::Error::EnvVar(VarError::NotPresent)),
+        value => Ok(value),
+    }
+}
+
+/// Reads an `S3_*` environment variable, falling back to the `R2_*` name which it had before any
+/// S3-compatible service could be used.
+fn s3_var(name: &str) -> AnyhowResult<String> {
+    [format!("S3_{name}"), format!("R2_{name}")]
+        .iter()
+        .find_map(|key| var(key).ok())
+        .with_context(|| format!("Missing environment variable `S3_{name}`"))
+}
@@ -1,36 +1,39 @@
-use std::{sync::Arc, thread, time::Duration as StdDuration};
+use std::{
+    collections::HashSet,
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+    thread,
+    time::Duration as StdDuration,
+};
 
-use anyhow::{Context as _, Result as AnyhowResult};
-use chrono::{DateTime, FixedOffset, NaiveTime, TimeDelta, Utc};
+use anyhow::{Context as _, Result as AnyhowResult, anyhow};
+use chrono::{FixedOffset, NaiveTime, TimeDelta, Utc};
 use jsonwebtoken::jwk::JwkSet;
 use libvips::VipsApp;
 use reqwest::{Client as ReqwestClient, header::CACHE_CONTROL};
-use sqlx::PgPool;
+use sqlx::{PgPool, postgres::PgListener};
 use tokio::{
     runtime::Handle,
-    sync::{
-        Mutex,
-        mpsc::{Receiver, error::TryRecvError},
-        oneshot::{self, Receiver as OneshotReceiver, Sender as OneshotSender},
-    },
+    sync::{Mutex, mpsc::Receiver},
 };
 use tokio_util::sync::CancellationToken;
 
 use crate::{
-    AppState, Config, GOOGLE_SIGNING_KEYS, R2Bucket, Thumbnailer,
-    database::{FilesTable, OrdersTable, SettingsTable},
-    schemas::{
-        Settings,
-        enums::{FileType, OrderStatus},
+    AppState, Bucket, Config, GOOGLE_SIGNING_KEYS, ThumbnailJob, Thumbnailer,
+    database::{FilesTable, OrdersTable, SettingsTable, ThumbnailFailuresTable},
+    schemas::{OrderStatusReason, Settings, ThumbnailJobStatus, enums::OrderStatus},
+    state::{
+        DraftOrderStore, INCOMING_ORDERS_CHANNEL, OAuthStates, ORDER_STATUS_CHANGES_CHANNEL,
+        OrderEvents, vips_version_check,
     },
-    state::{DraftOrderStore, OAuthStates, vips_version_check},
 };
 
+const ORPHAN_COLLECTOR_INTERVAL: StdDuration = StdDuration::from_secs(60 * 60);
+
 #[derive(Debug)]
 pub struct DaemonController {
     app_state: AppState,
     canceller: CancellationToken,
-    thumbnailer_canceller: Option<OneshotSender<()>>,
 }
 
 impl DaemonController {
@@ -39,16 +42,11 @@ impl DaemonController {
         Self {
             app_state,
             canceller: CancellationToken::new(),
-            thumbnailer_canceller: None,
         }
     }
 
     #[must_use]
-    pub fn start_all(
-        mut self,
-        handle: Handle,
-        thumbnailer_rx: Receiver<(String, FileType)>,
-    ) -> Self {
+    pub fn start_all(self, handle: Handle, thumbnailer_rx: Receiver<ThumbnailJob>) -> Self {
         tokio::task::Builder::new()
             .name("Google JWKS Fetcher")
             .spawn(fetch_google_jwks(
@@ -84,30 +82,54 @@ impl DaemonController {
             ))
             .unwrap();
 
+        tokio::task::Builder::new()
+            .name("Orphaned Objects Collector")
+            .spawn(collect_orphaned_objects(
+                self.app_state.config.clone(),
+                self.app_state.pool.clone(),
+                self.app_state.bucket.clone(),
+                self.app_state.draft_orders.clone(),
+                self.canceller.clone(),
+            ))
+            .unwrap();
+
+        tokio::task::Builder::new()
+            .name("Order Events Listener")
+            .spawn(listen_order_events(
+                self.app_state.pool.clone(),
+                self.app_state.order_events.clone(),
+                self.canceller.clone(),
+            ))
+            .unwrap();
+
+        let pool = self.app_state.pool.clone();
         let bucket = self.app_state.bucket.clone();
+        let thumbnailer = self.app_state.thumbnailer.clone();
         let thumbnail_size = self.app_state.config.thumbnail_size();
-        let (thumbnail_canceller_tx, thumbnail_canceller_rx) = oneshot::channel();
-        self.thumbnailer_canceller = Some(thumbnail_canceller_tx);
-        thread::spawn(move || {
-            thumbnailer_loop(
-                handle,
-                bucket,
-                thumbnail_size,
-                thumbnailer_rx,
-                thumbnail_canceller_rx,
-            )
-            .expect("`Thumbnailer` thread panicked");
-        });
+        let workers = self.app_state.config.thumbnailer_workers();
+        let token = self.canceller.clone();
+        thread::Builder::new()
+            .name(String::from("thumbnailer"))
+            .spawn(move || {
+                thumbnailer_pool(
+                    handle,
+                    pool,
+                    bucket,
+                    thumbnailer,
+                    thumbnail_size,
+                    workers,
+                    thumbnailer_rx,
+                    token,
+                )
+                .expect("`Thumbnailer` thread panicked");
+            })
+            .unwrap();
 
         self
     }
 
     pub fn stop_all(self) {
         self.canceller.cancel();
-        self.thumbnailer_canceller
-            .expect("Tried to cancel `Thumbnailer` thread but canceller does not exist")
-            .send(())
-            .expect("Already cancelled `Thumbnailer` thread");
     }
 }
 
@@ -167,11 +189,11 @@ async fn clean_oauth_states(oauth_states: Arc<Mutex<OAuthStates>>, token: Cancel
 }
 
 async fn clean_draft_orders(
-    bucket: R2Bucket,
+    bucket: Bucket,
     draft_orders: DraftOrderStore,
     token: CancellationToken,
 ) {
-    async fn inner(bucket: R2Bucket, draft_orders: DraftOrderStore) {
+    async fn inner(bucket: Bucket, draft_orders: DraftOrderStore) {
         loop {
             draft_orders.clear_expired(&bucket).await;
 
@@ -188,10 +210,10 @@ async fn clean_draft_orders(
 async fn flush_unfinished_orders(
     config: Arc<Config>,
     pool: PgPool,
-    bucket: R2Bucket,
+    bucket: Bucket,
     token: CancellationToken,
 ) -> AnyhowResult<()> {
-    async fn inner(offset: FixedOffset, pool: PgPool, bucket: R2Bucket) -> AnyhowResult<()> {
+    async fn inner(offset: FixedOffset, pool: PgPool, bucket: Bucket) -> AnyhowResult<()> {
         loop {
             let now = Utc::now().with_timezone(&offset);
             let Settings {
@@ -224,12 +246,24 @@ async fn flush_unfinished_orders(
                 .fetch_all(&mut tx)
                 .await?
                 .iter()
+                .filter(|order| order.status.can_transition_to(OrderStatus::Rejected, None))
                 .map(|order| order.id)
                 .collect::<Vec<_>>();
-            OrdersTable::update_statuses(&mut tx, &unfinished_orders, OrderStatus::Rejected)
-                .await?;
-            let old_files = FilesTable::fetch_object_keys_for_deletion(&mut tx, now).await?;
-            bucket.delete_files(&old_files).await?;
+            OrdersTable::update_statuses(
+                &mut tx,
+                &unfinished_orders,
+                OrderStatus::Rejected,
+                &OrderStatusReason::not_reviewed_before_close(),
+            )
+            .await?;
+            let (file_ids, expired_files) = FilesTable::fetch_expired_for_purging(&mut tx)
+                .await?
+                .into_iter()
+                .map(|(file_id, object_key, filetype)| (file_id, (object_key, filetype)))
+                .collect::<(Vec<_>, Vec<_>)>();
+            bucket.delete_files(&expired_files).await?;
+            FilesTable::set_purged(&mut tx, &file_ids).await?;
+            tracing::info!("purged {} expired file(s)", file_ids.len());
             SettingsTable::set_latest_orders_flushed_at(&mut tx).await?;
             tx.commit().await?;
         }
@@ -241,63 +275,232 @@ async fn flush_unfinished_orders(
     }
 }
 
+async fn collect_orphaned_objects(
+    config: Arc<Config>,
+    pool: PgPool,
+    bucket: Bucket,
+    draft_orders: DraftOrderStore,
+    token: CancellationToken,
+) {
+    async fn collect(
+        pool: &PgPool,
+        bucket: &Bucket,
+        draft_orders: &DraftOrderStore,
+        grace_period: TimeDelta,
+        dry_run: bool,
+    ) -> AnyhowResult<()> {
+        // Drafts are read before the files table, so that a draft which is built in between is
+        // still seen in the latter
+        let mut object_keys = draft_orders
+            .object_keys()
+            .await
+            .into_iter()
+            .collect::<HashSet<_>>();
+        object_keys.extend(FilesTable::fetch_all_object_keys(&mut *(pool.acquire().await?)).await?);
+
+        let orphans = bucket.find_orphans(&object_keys, grace_period).await?;
+        if dry_run {
+            for orphan in &orphans {
+                tracing::info!(object = %orphan, "found orphaned object");
+            }
+            tracing::info!(
+                "found {} orphaned object(s), not deleting in dry run",
+                orphans.len()
+            );
+        } else if !orphans.is_empty() {
+            bucket.delete_objects(&orphans).await?;
+            tracing::info!("deleted {} orphaned object(s)", orphans.len());
+        }
+
+        Ok(())
+    }
+
+    async fn inner(
+        grace_period: TimeDelta,
+        dry_run: bool,
+        pool: PgPool,
+        bucket: Bucket,
+        draft_orders: DraftOrderStore,
+    ) {
+        loop {
+            if let Err(error) = collect(&pool, &bucket, &draft_orders, grace_period, dry_run).await
+            {
+                tracing::warn!(%error, "failed to collect orphaned objects");
+            }
+
+            tokio::time::sleep(ORPHAN_COLLECTOR_INTERVAL).await;
+        }
+    }
+
+    tokio::select! {
+        () = token.cancelled() => (),
+        res = inner(
+            TimeDelta::from_std(config.orphan_grace_period()).unwrap_or(TimeDelta::MAX),
+            config.orphan_collector_dry_run(),
+            pool,
+            bucket,
+            draft_orders,
+        ) => res,
+    }
+}
+
+#[tracing::instrument(skip_all, err)]
+async fn listen_order_events(
+    pool: PgPool,
+    order_events: OrderEvents,
+    token: CancellationToken,
+) -> AnyhowResult<()> {
+    async fn inner(pool: PgPool, order_events: OrderEvents) -> AnyhowResult<()> {
+        let mut listener = PgListener::connect_with(&pool).await?;
+        listener
+            .listen_all([ORDER_STATUS_CHANGES_CHANNEL, INCOMING_ORDERS_CHANNEL])
+            .await?;
+
+        loop {
+            // `PgListener` transparently reconnects on the next call if the connection is lost
+            match listener.recv().await {
+                Ok(notification) => {
+                    order_events
+                        .dispatch(notification.channel(), notification.payload())
+                        .ok();
+                }
+                Err(error) => {
+                    tracing::warn!(%error, "failed to receive order events, retrying in 5 seconds");
+                    tokio::time::sleep(StdDuration::from_secs(5)).await;
+                }
+            }
+        }
+    }
+
+    tokio::select! {
+        () = token.cancelled() => Ok(()),
+        res = inner(pool, order_events) => res,
+    }
+}
+
+/// Runs a pool of thumbnailer workers, restarting any worker which panics. `libvips` itself is
+/// only initialised once, since it cannot be safely re-initialised after being shut down.
 #[allow(clippy::needless_pass_by_value)]
 #[tracing::instrument(skip_all, err)]
-fn thumbnailer_loop(
+#[allow(clippy::too_many_arguments)]
+fn thumbnailer_pool(
     handle: Handle,
-    bucket: R2Bucket,
+    pool: PgPool,
+    bucket: Bucket,
+    thumbnailer: Thumbnailer,
     thumbnail_size: i32,
-    mut thumbnailer_rx: Receiver<(String, FileType)>,
-    mut token: OneshotReceiver<()>,
+    workers: usize,
+    thumbnailer_rx: Receiver<ThumbnailJob>,
+    token: CancellationToken,
 ) -> AnyhowResult<()> {
-    let mut processed: Vec<(Arc<str>, DateTime<Utc>)> = Vec::new();
     let vips_app = VipsApp::new("thumbnailer", false)?;
     vips_app.cache_set_max(16); // Max operations
     vips_app.cache_set_max_files(0); // Max open files
     vips_app.cache_set_max_mem(256); // Max allocation
-    vips_app.concurrency_set(1); // Max workers
+    vips_app.concurrency_set(1); // Max threads per operation
     vips_version_check(vips_app.version_string()?)?;
 
-    while let Ok(()) | Err(oneshot::error::TryRecvError::Empty) = token.try_recv() {
-        match thumbnailer_rx.try_recv() {
-            Ok(data) => {
-                if let Err(pos) =
-                    processed.binary_search_by_key(&data.0.as_str(), |(string, _)| string)
-                {
-                    let to_be_processed: (Arc<str>, DateTime<Utc>) =
-                        (Arc::from(data.0.as_str()), Utc::now());
-                    processed.insert(pos, (Arc::clone(&to_be_processed.0), to_be_processed.1));
-
-                    tracing::info_span!(
-                        "thumbnailer",
-                        object_key = %to_be_processed.0,
-                        elapsed = tracing::field::Empty,
-                    )
-                    .in_scope(|| -> AnyhowResult<()> {
-                        tracing::info!("start processing thumbnail");
-                        let elapsed = Thumbnailer::process_single_thumbnail(
+    let thumbnailer_rx = Mutex::new(thumbnailer_rx);
+    thread::scope(|scope| {
+        for worker in 0..workers {
+            thread::Builder::new()
+                .name(format!("thumbnailer-{worker}"))
+                .spawn_scoped(scope, || {
+                    while panic::catch_unwind(AssertUnwindSafe(|| {
+                        thumbnailer_worker(
                             &handle,
+                            &pool,
                             &bucket,
+                            &thumbnailer,
                             thumbnail_size,
-                            &to_be_processed.0,
-                            data.1,
-                        )?;
-                        tracing::info!(?elapsed, "finished thumbnail processing");
+                            &thumbnailer_rx,
+                            &token,
+                        );
+                    }))
+                    .is_err()
+                    {
+                        tracing::error!("thumbnailer worker panicked, restarting");
+                        thread::sleep(StdDuration::from_secs(1));
+                    }
+                })
+                .expect("Failed to spawn `Thumbnailer` worker");
+        }
+    });
 
-                        Ok(())
-                    })?;
-                }
-            }
-            Err(TryRecvError::Empty) => {
-                let now = Utc::now(); // I should really find a better way
-                processed.retain(|(_, created_at)| (now - created_at).num_hours() <= 1);
+    drop(vips_app);
+    Ok(())
+}
 
-                thread::sleep(StdDuration::from_millis(1));
+fn thumbnailer_worker(
+    handle: &Handle,
+    pool: &PgPool,
+    bucket: &Bucket,
+    thumbnailer: &Thumbnailer,
+    thumbnail_size: i32,
+    thumbnailer_rx: &Mutex<Receiver<ThumbnailJob>>,
+    token: &CancellationToken,
+) {
+    // Workers take turns waiting on the queue, so idle workers sleep instead of spinning
+    while let Some(job) = handle.block_on(async {
+        let mut thumbnailer_rx = thumbnailer_rx.lock().await;
+        tokio::select! {
+            job = thumbnailer_rx.recv() => job,
+            () = token.cancelled() => None,
+        }
+    }) {
+        let thumbnail_keys = job.thumbnail_keys();
+        for thumbnail_key in &thumbnail_keys {
+            thumbnailer.set_status(thumbnail_key.clone(), ThumbnailJobStatus::Processing);
+        }
+        thumbnailer.clear_expired_statuses();
+
+        let span = tracing::info_span!(
+            "thumbnailer",
+            object_key = %job.object_key,
+            pages = ?job.pages,
+            attempt = job.attempts() + 1,
+            queue_depth = thumbnailer.queue_depth(),
+            elapsed = tracing::field::Empty,
+        );
+        let _enter = span.enter();
+        tracing::info!("start processing thumbnail");
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            Thumbnailer::process_thumbnails(handle, bucket, thumbnail_size, &job)
+        }))
+        .unwrap_or_else(|_| Err(anyhow!("Thumbnail processing panicked")));
+
+        let status = match result {
+            Ok(elapsed) => {
+                tracing::info!(?elapsed, "finished thumbnail processing");
+                ThumbnailJobStatus::Done
             }
-            Err(TryRecvError::Disconnected) => break,
+            Err(err) => {
+                let reason = err.to_string();
+                match thumbnailer.schedule_retry(handle, job) {
+                    Ok(backoff) => {
+                        tracing::warn!(?err, ?backoff, "retrying thumbnail processing");
+                        ThumbnailJobStatus::Queued
+                    }
+                    Err(job) => {
+                        tracing::error!(?err, "failed thumbnail processing");
+                        if let Err(err) = handle.block_on(async {
+                            ThumbnailFailuresTable::create_new(
+                                &mut *(pool.acquire().await?),
+                                &job,
+                                &reason,
+                            )
+                            .await
+                        }) {
+                            tracing::error!(?err, "failed to record thumbnail failure");
+                        }
+
+                        ThumbnailJobStatus::Failed { reason }
+                    }
+                }
+            }
+        };
+        for thumbnail_key in thumbnail_keys {
+            thumbnailer.set_status(thumbnail_key, status.clone());
         }
     }
-
-    drop(vips_app);
-    Ok(())
 }
@@ -1,11 +1,23 @@
+mod bindings;
+mod edits;
 mod files;
+mod laminations;
 mod orders;
 mod papers;
+mod pricing;
+mod services;
 mod settings;
+mod thumbnails;
 mod users;
 
+pub use bindings::BindingsTable;
+pub use edits::OrderEditor;
 pub use files::FilesTable;
+pub use laminations::LaminationsTable;
 pub use orders::OrdersTable;
 pub use papers::PapersTable;
+pub use pricing::PricingTable;
+pub use services::{ServiceCatalogue, ServicesTable};
 pub use settings::SettingsTable;
+pub use thumbnails::ThumbnailFailuresTable;
 pub use users::UsersTable;
@@ -0,0 +1,192 @@
+use sqlx::PgConnection;
+
+use crate::{
+    SqlxResult,
+    schemas::{
+        Binding, BindingColour, BindingColourCreate, BindingColourId, BindingCreate, BindingId,
+        BindingPaper, BindingUpdate,
+    },
+};
+
+pub struct BindingsTable;
+
+impl BindingsTable {
+    #[tracing::instrument(skip_all, err)]
+    pub async fn create_new(
+        conn: &mut PgConnection,
+        binding: &BindingCreate,
+    ) -> SqlxResult<(BindingId, Vec<BindingColour>)> {
+        let binding_id = sqlx::query_scalar(
+            "INSERT INTO bindings (name, is_available) VALUES ($1, $2) RETURNING id",
+        )
+        .bind(binding.name.as_str())
+        .bind(binding.is_available)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        let colours_len = binding.colours.len();
+        let mut colours = Vec::with_capacity(colours_len);
+        let mut is_available_vec = Vec::with_capacity(colours_len);
+        binding.colours.iter().for_each(|binding_colour| {
+            colours.push(binding_colour.colour.as_str());
+            is_available_vec.push(binding_colour.is_available);
+        });
+
+        let binding_colours = sqlx::query_as(
+            "\
+            INSERT INTO binding_colours (binding_id, colour, is_available)\
+            SELECT $1, * FROM UNNEST($2::text[], $3::bool[])\
+            RETURNING id, colour, is_available\
+            ",
+        )
+        .bind(binding_id)
+        .bind(&colours)
+        .bind(&is_available_vec)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        Self::set_papers(conn, binding_id, &binding.papers).await?;
+
+        Ok((binding_id, binding_colours))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    pub async fn create_new_colour(
+        conn: &mut PgConnection,
+        binding_id: BindingId,
+        binding_colour: &BindingColourCreate,
+    ) -> SqlxResult<BindingColourId> {
+        sqlx::query_scalar(
+            "\
+            INSERT INTO binding_colours (binding_id, colour, is_available)\
+            VALUES ($1, $2, $3) RETURNING id\
+            ",
+        )
+        .bind(binding_id)
+        .bind(binding_colour.colour.as_str())
+        .bind(binding_colour.is_available)
+        .fetch_one(conn)
+        .await
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    pub async fn fetch_all(conn: &mut PgConnection) -> SqlxResult<Vec<Binding>> {
+        sqlx::query_as(
+            "\
+            SELECT b.id, b.name, b.is_available, c.colours, p.papers \
+            FROM bindings AS b \
+                JOIN LATERAL (SELECT \
+                    COALESCE(ARRAY_AGG(ROW(\
+                        id, colour, is_available\
+                    )::binding_colour ORDER BY colour), '{}') AS colours \
+                FROM binding_colours \
+                WHERE binding_id = b.id) AS c ON true \
+                JOIN LATERAL (SELECT \
+                    COALESCE(ARRAY_AGG(ROW(\
+                        paper_id, coverable\
+                    )::binding_paper ORDER BY paper_id), '{}') AS papers \
+                FROM bindings_papers \
+                WHERE binding_id = b.id) AS p ON true \
+            ORDER BY b.name\
+            ",
+        )
+        .fetch_all(conn)
+        .await
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    pub async fn update(
+        conn: &mut PgConnection,
+        binding_id: BindingId,
+        binding: &BindingUpdate,
+    ) -> SqlxResult<()> {
+        sqlx::query("UPDATE bindings SET name = $2, is_available = $3 WHERE id = $1")
+            .bind(binding_id)
+            .bind(binding.name.as_str())
+            .bind(binding.is_available)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    pub async fn update_colour(
+        conn: &mut PgConnection,
+        binding_id: BindingId,
+        binding_colour_id: BindingColourId,
+        binding_colour: &BindingColourCreate,
+    ) -> SqlxResult<()> {
+        sqlx::query(
+            "\
+            UPDATE binding_colours SET colour = $3, is_available = $4 \
+            WHERE id = $1 AND binding_id = $2\
+            ",
+        )
+        .bind(binding_colour_id)
+        .bind(binding_id)
+        .bind(binding_colour.colour.as_str())
+        .bind(binding_colour.is_available)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Replaces the paper compatibility matrix of a binding.
+    #[tracing::instrument(skip_all, err)]
+    pub async fn set_papers(
+        conn: &mut PgConnection,
+        binding_id: BindingId,
+        papers: &[BindingPaper],
+    ) -> SqlxResult<()> {
+        sqlx::query("DELETE FROM bindings_papers WHERE binding_id = $1")
+            .bind(binding_id)
+            .execute(&mut *conn)
+            .await?;
+
+        let (paper_ids, coverable_vec): (Vec<_>, Vec<_>) = papers
+            .iter()
+            .map(|binding_paper| (binding_paper.paper_id, binding_paper.coverable))
+            .unzip();
+
+        sqlx::query(
+            "\
+            INSERT INTO bindings_papers (binding_id, paper_id, coverable)\
+            SELECT $1, * FROM UNNEST($2::integer[], $3::bool[])\
+            ",
+        )
+        .bind(binding_id)
+        .bind(&paper_ids)
+        .bind(&coverable_vec)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    pub async fn delete(conn: &mut PgConnection, binding_id: BindingId) -> SqlxResult<()> {
+        sqlx::query("DELETE FROM bindings WHERE id = $1")
+            .bind(binding_id)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    pub async fn delete_colour(
+        conn: &mut PgConnection,
+        binding_id: BindingId,
+        binding_colour_id: BindingColourId,
+    ) -> SqlxResult<()> {
+        sqlx::query("DELETE FROM binding_colours WHERE id = $1 AND binding_id = $2")
+            .bind(binding_colour_id)
+            .bind(binding_id)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+}
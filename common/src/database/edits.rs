@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use sqlx::PgConnection;
+
+use crate::{
+    AppError,
+    error::BadRequestError,
+    schemas::{
+        DetailedOrder, File, FileCreate, FileRange, FileRangeCreate, OrderId, OrderUpdate, UserId,
+        enums::OrderStatus,
+    },
+    state::{DraftOrderStore, MAX_FILE_RANGES},
+};
+
+use super::{FilesTable, OrdersTable, PricingTable};
+
+pub struct OrderEditor;
+
+impl OrderEditor {
+    /// Applies the changes of the owner to an order which is still being reviewed, validating
+    /// them as if the order was being built and quoting it again. Every edit is recorded along
+    /// with the previous options of the order.
+    #[tracing::instrument(skip_all, err)]
+    pub async fn edit(
+        conn: &mut PgConnection,
+        order_id: OrderId,
+        actor_id: UserId,
+        changes: OrderUpdate,
+    ) -> Result<DetailedOrder, AppError> {
+        if !matches!(
+            OrdersTable::fetch_status_for_update(&mut *conn, order_id).await?,
+            OrderStatus::Reviewing
+        ) {
+            return Err(AppError::BadRequest(
+                BadRequestError::UnprocessableStatusUpdate,
+            ));
+        }
+
+        let DetailedOrder {
+            notes,
+            files,
+            services,
+            quote,
+            ..
+        } = OrdersTable::query_detailed(order_id)
+            .fetch_one(&mut *conn)
+            .await?;
+        let previous = serde_json::json!({
+            "notes": notes,
+            "files": files,
+            "services": services,
+            "quote": quote,
+        });
+        let changes_json = serde_json::to_string(&changes).map_err(|err| anyhow!(err))?;
+
+        let malformed = || {
+            AppError::BadRequest(BadRequestError::MalformedJson(
+                "Request data contains malformed data for files and/or services".into(),
+            ))
+        };
+        let OrderUpdate {
+            notes: notes_update,
+            files: files_update,
+            services: services_update,
+        } = changes;
+        let mut files_update = files_update.unwrap_or_default();
+        let updated_file_ids = files_update
+            .iter()
+            .map(|file_update| file_update.id)
+            .collect::<Vec<_>>();
+        if !files_update.iter().all(|file_update| {
+            files.iter().any(|file| file.id == file_update.id)
+                && !file_update.ranges.is_empty()
+                && file_update.ranges.len() <= MAX_FILE_RANGES
+        }) {
+            return Err(malformed());
+        }
+
+        let is_services_updated = services_update.is_some();
+        let services = services_update.unwrap_or(services);
+        if !services.iter().all(|service| {
+            service
+                .file_ids
+                .iter()
+                .all(|file_id| files.iter().any(|file| file.id == *file_id))
+        }) {
+            return Err(malformed());
+        }
+
+        let page_counts = files
+            .iter()
+            .map(|file| {
+                (
+                    file.id,
+                    file.page_count
+                        .and_then(|page_count| u32::try_from(page_count).ok())
+                        .unwrap_or(1),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        let mut file_creates = files
+            .iter()
+            .map(|file| {
+                let ranges = match files_update
+                    .iter()
+                    .position(|file_update| file_update.id == file.id)
+                {
+                    Some(position) => files_update.swap_remove(position).ranges,
+                    None => file
+                        .ranges
+                        .iter()
+                        .map(|file_range| {
+                            Some(FileRangeCreate {
+                                range: file_range.range.clone(),
+                                copies: file_range.copies,
+                                paper_variant_id: file_range.paper_variant_id?,
+                                paper_orientation: file_range.paper_orientation,
+                                is_colour: file_range.is_colour,
+                                is_double_sided: file_range.is_double_sided,
+                            })
+                        })
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(malformed)?,
+                };
+
+                Ok(FileCreate {
+                    id: file.id,
+                    filename: file.filename.clone(),
+                    ranges,
+                })
+            })
+            .collect::<Result<Vec<_>, AppError>>()?;
+        DraftOrderStore::validate_contents(&mut *conn, &mut file_creates, &services).await?;
+        DraftOrderStore::check_page_ranges(&file_creates, &page_counts)?;
+
+        let files = files
+            .into_iter()
+            .zip(file_creates)
+            .map(|(file, file_create)| File {
+                ranges: file_create
+                    .ranges
+                    .into_iter()
+                    .map(FileRange::from)
+                    .collect(),
+                ..file
+            })
+            .collect::<Vec<_>>();
+        let notes = match notes_update {
+            Some(notes_update) => Some(notes_update.trim().to_string()).filter(|n| !n.is_empty()),
+            None => notes,
+        };
+        let quote = PricingTable::fetch_rates(&mut *conn)
+            .await?
+            .quote(&files, &services);
+
+        OrdersTable::update_options(&mut *conn, order_id, notes.as_deref(), quote).await?;
+        for file in files
+            .iter()
+            .filter(|file| updated_file_ids.contains(&file.id))
+        {
+            FilesTable::replace_ranges(&mut *conn, file).await?;
+        }
+        if is_services_updated {
+            OrdersTable::replace_services(&mut *conn, order_id, &services).await?;
+        }
+        OrdersTable::create_edit(
+            &mut *conn,
+            order_id,
+            actor_id,
+            &previous.to_string(),
+            &changes_json,
+        )
+        .await?;
+
+        Ok(OrdersTable::query_detailed(order_id)
+            .fetch_one(conn)
+            .await?)
+    }
+}
@@ -1,4 +1,3 @@
-use chrono::{DateTime, FixedOffset};
 use futures::stream::BoxStream;
 use sqlx::PgConnection;
 
@@ -10,7 +9,6 @@ use crate::{
 pub struct FilesTable;
 
 impl FilesTable {
-    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
     #[tracing::instrument(skip_all, err)]
     pub async fn create_new(
         conn: &mut PgConnection,
@@ -20,8 +18,9 @@ impl FilesTable {
     ) -> SqlxResult<()> {
         sqlx::query(
             "\
-                INSERT INTO files (id, order_id, object_key, filename, filetype, filesize, index)\
-                VALUES ($1, $2, $3, $4, $5, $6, $7)\
+                INSERT INTO files (\
+                    id, order_id, object_key, filename, filetype, filesize, page_count, index\
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)\
                 ",
         )
         .bind(file.id)
@@ -30,10 +29,27 @@ impl FilesTable {
         .bind(file.filename.as_str())
         .bind(file.filetype)
         .bind(file.filesize)
+        .bind(file.page_count)
         .bind(index)
         .execute(&mut *conn)
         .await?;
 
+        Self::create_ranges(conn, file).await
+    }
+
+    /// Replaces every page range of the given file with its current ones.
+    #[tracing::instrument(skip_all, err)]
+    pub(crate) async fn replace_ranges(conn: &mut PgConnection, file: &File) -> SqlxResult<()> {
+        sqlx::query("DELETE FROM file_ranges WHERE file_id = $1")
+            .bind(file.id)
+            .execute(&mut *conn)
+            .await?;
+
+        Self::create_ranges(conn, file).await
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    async fn create_ranges(conn: &mut PgConnection, file: &File) -> SqlxResult<()> {
         let ranges_len = file.ranges.len();
         let mut ids = Vec::with_capacity(ranges_len);
         let mut ranges = Vec::with_capacity(ranges_len);
@@ -91,7 +107,9 @@ impl FilesTable {
     ) -> SqlxResult<FileMetadata> {
         sqlx::query_as(
             "\
-            SELECT f.id, f.object_key, f.filename, f.filetype \
+            SELECT \
+                f.id, f.object_key, f.filename, f.filetype, f.page_count,\
+                f.purged_at IS NOT NULL AS is_purged \
             FROM files AS f \
                 JOIN orders AS o ON o.id = f.order_id \
             WHERE f.id = $1 AND f.order_id = $2 \
@@ -107,10 +125,12 @@ impl FilesTable {
     pub fn stream_all_for_metadata_from_order(
         conn: &mut PgConnection,
         order_id: OrderId,
-    ) -> BoxStream<SqlxResult<FileMetadata>> {
+    ) -> BoxStream<'_, SqlxResult<FileMetadata>> {
         sqlx::query_as(
             "\
-            SELECT f.id, f.object_key, f.filename, f.filetype \
+            SELECT \
+                f.id, f.object_key, f.filename, f.filetype, f.page_count,\
+                f.purged_at IS NOT NULL AS is_purged \
             FROM files AS f \
                 JOIN orders AS o ON o.id = f.order_id \
             WHERE f.order_id = $1 \
@@ -122,21 +142,50 @@ impl FilesTable {
     }
 
     #[tracing::instrument(skip_all, err)]
-    pub(crate) async fn fetch_object_keys_for_deletion(
+    pub(crate) async fn fetch_all_object_keys(conn: &mut PgConnection) -> SqlxResult<Vec<String>> {
+        sqlx::query_scalar("SELECT object_key FROM files")
+            .fetch_all(conn)
+            .await
+    }
+
+    /// Fetches the files which have outlived the retention period configured in the settings for
+    /// the status of their order. Files of orders which are still in progress are always kept.
+    #[tracing::instrument(skip_all, err)]
+    pub(crate) async fn fetch_expired_for_purging(
         conn: &mut PgConnection,
-        timestamp: DateTime<FixedOffset>,
-    ) -> SqlxResult<Vec<(String, FileType)>> {
+    ) -> SqlxResult<Vec<(FileId, String, FileType)>> {
         Ok(sqlx::query!(
             "\
-            SELECT object_key, filetype as \"filetype: FileType\" \
-            FROM files WHERE created_at < $1\
+            SELECT f.id AS \"id: FileId\", f.object_key, f.filetype AS \"filetype: FileType\" \
+            FROM files AS f \
+                JOIN orders AS o ON o.id = f.order_id \
+                JOIN LATERAL (SELECT MAX(created_at) AS finished_at \
+                FROM order_status_updates \
+                WHERE order_id = o.id AND status = o.status) AS u ON true \
+                CROSS JOIN settings AS s \
+            WHERE f.purged_at IS NULL AND COALESCE(u.finished_at, o.created_at) + make_interval(\
+                days => CASE o.status \
+                    WHEN 'completed' THEN s.completed_file_retention_days \
+                    WHEN 'rejected' THEN s.rejected_file_retention_days \
+                    WHEN 'cancelled' THEN s.cancelled_file_retention_days \
+                END\
+            ) < CURRENT_TIMESTAMP\
             ",
-            timestamp,
         )
         .fetch_all(conn)
         .await?
         .into_iter()
-        .map(|file| (file.object_key, file.filetype))
+        .map(|file| (file.id, file.object_key, file.filetype))
         .collect())
     }
+
+    #[tracing::instrument(skip_all, err)]
+    pub(crate) async fn set_purged(conn: &mut PgConnection, file_ids: &[FileId]) -> SqlxResult<()> {
+        sqlx::query("UPDATE files SET purged_at = CURRENT_TIMESTAMP WHERE id = ANY($1)")
+            .bind(file_ids)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
 }
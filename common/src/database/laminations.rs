@@ -0,0 +1,127 @@
+use chrono::Utc;
+use sqlx::PgConnection;
+
+use crate::{
+    SqlxResult,
+    schemas::{
+        LaminatablePaperVariant, LaminationFilm, LaminationFilmCreate, LaminationFilmId,
+        PaperVariantId,
+    },
+};
+
+pub struct LaminationsTable;
+
+impl LaminationsTable {
+    #[tracing::instrument(skip_all, err)]
+    pub async fn create_new_film(
+        conn: &mut PgConnection,
+        film: &LaminationFilmCreate,
+    ) -> SqlxResult<LaminationFilmId> {
+        sqlx::query_scalar(
+            "INSERT INTO lamination_films (name, is_available) VALUES ($1, $2) RETURNING id",
+        )
+        .bind(film.name.as_str())
+        .bind(film.is_available)
+        .fetch_one(conn)
+        .await
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    pub async fn fetch_films(conn: &mut PgConnection) -> SqlxResult<Vec<LaminationFilm>> {
+        sqlx::query_as("SELECT id, name, is_available FROM lamination_films ORDER BY name")
+            .fetch_all(conn)
+            .await
+    }
+
+    /// Fetches whether lamination is offered at all.
+    #[tracing::instrument(skip_all, err)]
+    pub async fn fetch_is_serviceable(conn: &mut PgConnection) -> SqlxResult<bool> {
+        sqlx::query_scalar("SELECT is_serviceable FROM lamination_options")
+            .fetch_one(conn)
+            .await
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    pub async fn fetch_paper_variants(
+        conn: &mut PgConnection,
+    ) -> SqlxResult<Vec<LaminatablePaperVariant>> {
+        sqlx::query_as(
+            "\
+            SELECT \
+                v.id, v.paper_id, p.name AS paper_name, v.name, v.is_available,\
+                l.paper_variant_id IS NOT NULL AS is_laminatable \
+            FROM paper_variants AS v \
+                JOIN papers AS p ON p.id = v.paper_id \
+                LEFT JOIN lamination_paper_variants AS l ON l.paper_variant_id = v.id \
+            ORDER BY p.is_default DESC, p.name, v.is_default DESC, v.name\
+            ",
+        )
+        .fetch_all(conn)
+        .await
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    pub async fn update_film(
+        conn: &mut PgConnection,
+        film_id: LaminationFilmId,
+        film: &LaminationFilmCreate,
+    ) -> SqlxResult<()> {
+        sqlx::query("UPDATE lamination_films SET name = $2, is_available = $3 WHERE id = $1")
+            .bind(film_id)
+            .bind(film.name.as_str())
+            .bind(film.is_available)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    pub async fn set_is_serviceable(
+        conn: &mut PgConnection,
+        is_serviceable: bool,
+    ) -> SqlxResult<()> {
+        sqlx::query("UPDATE lamination_options SET updated_at = $1, is_serviceable = $2")
+            .bind(Utc::now())
+            .bind(is_serviceable)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Marks exactly the given paper variants as laminatable, clearing every other variant and
+    /// ignoring variants which do not exist.
+    #[tracing::instrument(skip_all, err)]
+    pub async fn set_laminatable_paper_variants(
+        conn: &mut PgConnection,
+        paper_variant_ids: &[PaperVariantId],
+    ) -> SqlxResult<()> {
+        sqlx::query("DELETE FROM lamination_paper_variants WHERE paper_variant_id <> ALL($1)")
+            .bind(paper_variant_ids)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query(
+            "\
+            INSERT INTO lamination_paper_variants (paper_variant_id)\
+            SELECT id FROM paper_variants WHERE id = ANY($1) \
+            ON CONFLICT DO NOTHING\
+            ",
+        )
+        .bind(paper_variant_ids)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    pub async fn delete_film(conn: &mut PgConnection, film_id: LaminationFilmId) -> SqlxResult<()> {
+        sqlx::query("DELETE FROM lamination_films WHERE id = $1")
+            .bind(film_id)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -1,5 +1,5 @@
-use chrono::{DateTime, FixedOffset, Utc};
-use sqlx::{PgConnection, Postgres, QueryBuilder};
+use chrono::{DateTime, FixedOffset, TimeDelta, Utc};
+use sqlx::{FromRow, PgConnection, Postgres, QueryBuilder};
 use uuid::Uuid;
 
 use crate::{
@@ -10,9 +10,11 @@ use crate::{
     error::ForbiddenError,
     request::PageKey,
     schemas::{
-        CompactOrder, DetailedOrder, OrderId, OrderStatusUpdate, ServiceId, UserId,
+        CompactOrder, DetailedOrder, IncomingOrderEvent, OrderId, OrderStatusReason,
+        OrderStatusUpdate, Service, ServiceId, UserId,
         enums::{OrderStatus, UserRole},
     },
+    state::{INCOMING_ORDERS_CHANNEL, ORDER_STATUS_CHANGES_CHANNEL},
 };
 
 pub struct OrdersTable;
@@ -23,8 +25,8 @@ impl OrdersTable {
     pub async fn create_new(conn: &mut PgConnection, order: &DetailedOrder) -> SqlxResult<()> {
         sqlx::query(
             "\
-            INSERT INTO orders (id, created_at, owner_id, order_number, status, notes)\
-            VALUES ($1, $2, $3, $4, $5, $6)\
+            INSERT INTO orders (id, created_at, owner_id, order_number, status, quote, notes)\
+            VALUES ($1, $2, $3, $4, $5, $6, $7)\
             ",
         )
         .bind(order.id)
@@ -32,36 +34,54 @@ impl OrdersTable {
         .bind(order.owner_id)
         .bind(order.order_number.as_str())
         .bind(OrderStatus::Reviewing)
+        .bind(order.quote)
         .bind(order.notes.as_ref())
         .execute(&mut *conn)
         .await?;
 
-        sqlx::query(
+        let status_update_id: i64 = sqlx::query_scalar(
             "\
-            INSERT INTO order_status_updates (created_at, order_id, status)\
-            VALUES ($1, $2, $3)\
+            INSERT INTO order_status_updates (created_at, order_id, status, actor_id)\
+            VALUES ($1, $2, $3, $4) RETURNING id\
             ",
         )
         .bind(Utc::now())
         .bind(order.id)
         .bind(OrderStatus::Reviewing)
-        .execute(&mut *conn)
+        .bind(order.owner_id)
+        .fetch_one(&mut *conn)
         .await?;
 
         for (index, file) in order.files.iter().enumerate() {
             FilesTable::create_new(conn, order.id, file, index as i32).await?;
         }
 
-        for (index, service) in order.services.iter().enumerate() {
+        Self::create_services(&mut *conn, order.id, &order.services).await?;
+
+        Self::notify_status_changes(&mut *conn, &[order.id]).await?;
+        Self::notify_incoming_order_events(conn, &[status_update_id]).await?;
+
+        Ok(())
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    async fn create_services(
+        conn: &mut PgConnection,
+        order_id: OrderId,
+        services: &[Service],
+    ) -> SqlxResult<()> {
+        for (index, service) in services.iter().enumerate() {
             let service_id: ServiceId = sqlx::query_scalar(
                 "\
-                INSERT INTO services (order_id, type, binding_colour_id, notes, index)\
-                VALUES ($1, $2, $3, $4, $5) RETURNING id\
+                INSERT INTO services (\
+                    order_id, type, binding_colour_id, lamination_film_id, notes, index\
+                ) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id\
                 ",
             )
-            .bind(order.id)
+            .bind(order_id)
             .bind(service.r#type)
             .bind(service.binding_colour_id.as_ref())
+            .bind(service.lamination_film_id.as_ref())
             .bind(service.notes.as_ref())
             .bind(index as i32)
             .fetch_one(&mut *conn)
@@ -73,7 +93,7 @@ impl OrdersTable {
                 SELECT $1, $2, * FROM UNNEST($3::uuid[])\
                 ",
             )
-            .bind(order.id)
+            .bind(order_id)
             .bind(service_id)
             .bind(&service.file_ids)
             .execute(&mut *conn)
@@ -83,11 +103,78 @@ impl OrdersTable {
         Ok(())
     }
 
+    /// Sets the notes and quote of an order, clearing its price if the quote changed so that it is
+    /// set again once the new quote is accepted.
+    #[tracing::instrument(skip_all, err)]
+    pub async fn update_options(
+        conn: &mut PgConnection,
+        order_id: OrderId,
+        notes: Option<&str>,
+        quote: Option<i64>,
+    ) -> SqlxResult<()> {
+        sqlx::query(
+            "\
+            UPDATE orders SET \
+                notes = $1, quote = $2,\
+                price = CASE WHEN quote IS NOT DISTINCT FROM $2 THEN price END \
+            WHERE id = $3\
+            ",
+        )
+        .bind(notes)
+        .bind(quote)
+        .bind(order_id)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    pub async fn replace_services(
+        conn: &mut PgConnection,
+        order_id: OrderId,
+        services: &[Service],
+    ) -> SqlxResult<()> {
+        sqlx::query("DELETE FROM services WHERE order_id = $1")
+            .bind(order_id)
+            .execute(&mut *conn)
+            .await?;
+
+        Self::create_services(conn, order_id, services).await
+    }
+
+    /// Records an edit of an order, given the previous options of the order and the changes
+    /// which were applied to them as JSON.
+    #[tracing::instrument(skip_all, err)]
+    pub async fn create_edit(
+        conn: &mut PgConnection,
+        order_id: OrderId,
+        actor_id: UserId,
+        previous: &str,
+        changes: &str,
+    ) -> SqlxResult<()> {
+        sqlx::query(
+            "\
+            INSERT INTO order_edits (order_id, actor_id, previous, changes)\
+            VALUES ($1, $2, $3::text::jsonb, $4::text::jsonb)\
+            ",
+        )
+        .bind(order_id)
+        .bind(actor_id)
+        .bind(previous)
+        .bind(changes)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
     #[must_use]
     pub fn query_detailed(id: OrderId) -> DetailedOrderQuery {
         DetailedOrderQuery {
             id,
             with_owner: false,
+            with_actors: false,
         }
     }
 
@@ -115,6 +202,7 @@ impl OrdersTable {
             qb: QueryBuilder::new(query),
             count_qb: None,
             owner_id: None,
+            actor_id: None,
             statuses: &[],
             older_than_date: None,
             limit: None,
@@ -127,6 +215,8 @@ impl OrdersTable {
         conn: &mut PgConnection,
         order_id: OrderId,
         status: OrderStatus,
+        reason: &OrderStatusReason,
+        actor_id: UserId,
     ) -> SqlxResult<OrderStatusUpdate> {
         sqlx::query("UPDATE orders SET status = $1 WHERE id = $2")
             .bind(status)
@@ -134,16 +224,29 @@ impl OrdersTable {
             .execute(&mut *conn)
             .await?;
 
-        sqlx::query_as(
+        let (status_update_id, timestamp, status) = sqlx::query_as(
             "\
-            INSERT INTO order_status_updates (order_id, status)\
-            VALUES ($1, $2) RETURNING created_at, status\
+            INSERT INTO order_status_updates (order_id, status, reason, note, actor_id)\
+            VALUES ($1, $2, $3, $4, $5) RETURNING id, created_at, status\
             ",
         )
         .bind(order_id)
         .bind(status)
-        .fetch_one(conn)
-        .await
+        .bind(reason.reason.as_deref())
+        .bind(reason.note.as_deref())
+        .bind(actor_id)
+        .fetch_one(&mut *conn)
+        .await?;
+        Self::notify_status_changes(&mut *conn, &[order_id]).await?;
+        Self::notify_incoming_order_events(conn, &[status_update_id]).await?;
+
+        Ok(OrderStatusUpdate {
+            timestamp,
+            status,
+            reason: reason.reason.clone(),
+            note: reason.note.clone(),
+            actor_id: Some(actor_id),
+        })
     }
 
     #[tracing::instrument(skip_all, err)]
@@ -151,6 +254,7 @@ impl OrdersTable {
         conn: &mut PgConnection,
         order_ids: &[OrderId],
         status: OrderStatus,
+        reason: &OrderStatusReason,
     ) -> SqlxResult<()> {
         sqlx::query("UPDATE orders SET status = $1 WHERE id = ANY($2)")
             .bind(status)
@@ -158,20 +262,157 @@ impl OrdersTable {
             .execute(&mut *conn)
             .await?;
 
+        let status_update_ids: Vec<i64> = sqlx::query_scalar(
+            "\
+            INSERT INTO order_status_updates (order_id, status, reason, note)\
+            SELECT *, $2, $3, $4 FROM UNNEST($1::uuid[]) RETURNING id\
+            ",
+        )
+        .bind(order_ids)
+        .bind(status)
+        .bind(reason.reason.as_deref())
+        .bind(reason.note.as_deref())
+        .fetch_all(&mut *conn)
+        .await?;
+        Self::notify_status_changes(&mut *conn, order_ids).await?;
+        Self::notify_incoming_order_events(conn, &status_update_ids).await?;
+
+        Ok(())
+    }
+
+    /// Notifies listeners of [`ORDER_STATUS_CHANGES_CHANNEL`] of the latest status of each order.
+    /// Notifications are only delivered once the surrounding transaction commits.
+    #[tracing::instrument(skip_all, err)]
+    async fn notify_status_changes(
+        conn: &mut PgConnection,
+        order_ids: &[OrderId],
+    ) -> SqlxResult<()> {
         sqlx::query(
             "\
-            INSERT INTO order_status_updates (order_id, status)\
-            SELECT *, $2 FROM UNNEST($1::uuid[])\
+            SELECT pg_notify($1, json_build_object(\
+                'orderId', o.id, 'ownerId', o.owner_id, 'status', o.status,\
+                'timestamp', u.created_at\
+            )::text) \
+            FROM orders AS o \
+                JOIN LATERAL (SELECT created_at FROM order_status_updates \
+                WHERE order_id = o.id ORDER BY id DESC LIMIT 1) AS u ON true \
+            WHERE o.id = ANY($2)\
             ",
         )
+        .bind(ORDER_STATUS_CHANGES_CHANNEL)
         .bind(order_ids)
-        .bind(OrderStatus::Rejected)
         .execute(conn)
         .await?;
 
         Ok(())
     }
 
+    /// Notifies listeners of [`INCOMING_ORDERS_CHANNEL`] of orders that entered or left the
+    /// `Reviewing` status through the given status updates.
+    #[tracing::instrument(skip_all, err)]
+    async fn notify_incoming_order_events(
+        conn: &mut PgConnection,
+        status_update_ids: &[i64],
+    ) -> SqlxResult<()> {
+        let payloads = Self::fetch_incoming_order_events(&mut *conn, status_update_ids)
+            .await?
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|error| sqlx::Error::Encode(Box::new(error)))?;
+        if payloads.is_empty() {
+            return Ok(());
+        }
+
+        sqlx::query("SELECT pg_notify($1, payload) FROM UNNEST($2::text[]) AS payload")
+            .bind(INCOMING_ORDERS_CHANNEL)
+            .bind(&payloads)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Fetches the incoming order events that happened after `last_event_id`, up to a day ago.
+    ///
+    /// Event IDs are handed out when a transaction inserts its status update, not when it commits,
+    /// so an event may become visible after another event with a higher ID was already sent. The
+    /// events which happened up to a minute before `last_event_id` are therefore replayed as well,
+    /// and clients must ignore the events whose IDs they have already seen.
+    #[tracing::instrument(skip_all, err)]
+    pub async fn fetch_incoming_order_events_since(
+        conn: &mut PgConnection,
+        last_event_id: i64,
+    ) -> SqlxResult<Vec<IncomingOrderEvent>> {
+        let status_update_ids: Vec<i64> = sqlx::query_scalar(
+            "\
+            SELECT id FROM order_status_updates \
+            WHERE (id > $1 OR created_at >= (\
+                SELECT created_at - interval '1 minute' FROM order_status_updates WHERE id = $1\
+            )) AND created_at > $2 \
+            ORDER BY id\
+            ",
+        )
+        .bind(last_event_id)
+        .bind(Utc::now() - TimeDelta::days(1))
+        .fetch_all(&mut *conn)
+        .await?;
+
+        Self::fetch_incoming_order_events(conn, &status_update_ids).await
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn fetch_incoming_order_events(
+        conn: &mut PgConnection,
+        status_update_ids: &[i64],
+    ) -> SqlxResult<Vec<IncomingOrderEvent>> {
+        let rows: Vec<IncomingOrderEventRow> = sqlx::query_as(
+            "\
+            SELECT \
+                h.id, h.status, o.id AS order_id, o.created_at, o.order_number,\
+                (SELECT COUNT(id) FROM files WHERE order_id = o.id) AS files_count \
+            FROM (SELECT \
+                    id, order_id, status,\
+                    LAG(status) OVER (PARTITION BY order_id ORDER BY id) AS previous_status \
+                FROM order_status_updates \
+                WHERE order_id IN (\
+                    SELECT order_id FROM order_status_updates WHERE id = ANY($1)\
+                )) AS h \
+                JOIN orders AS o ON o.id = h.order_id \
+            WHERE h.id = ANY($1) AND (h.status = $2 OR h.previous_status = $2) \
+                ORDER BY h.id\
+            ",
+        )
+        .bind(status_update_ids)
+        .bind(OrderStatus::Reviewing)
+        .fetch_all(conn)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                if matches!(row.status, OrderStatus::Reviewing) {
+                    IncomingOrderEvent::Added {
+                        id: row.id,
+                        order: CompactOrder {
+                            id: row.order_id,
+                            created_at: row.created_at,
+                            order_number: row.order_number,
+                            status: row.status,
+                            files_count: row.files_count,
+                        },
+                    }
+                } else {
+                    IncomingOrderEvent::Removed {
+                        id: row.id,
+                        order_id: row.order_id,
+                        status: row.status,
+                    }
+                }
+            })
+            .collect())
+    }
+
     #[tracing::instrument(skip_all, err)]
     pub async fn update_price(
         conn: &mut PgConnection,
@@ -187,6 +428,17 @@ impl OrdersTable {
         Ok(())
     }
 
+    /// Sets the price of an order to its quote, unless a price has already been set.
+    #[tracing::instrument(skip_all, err)]
+    pub async fn accept_quote(conn: &mut PgConnection, order_id: OrderId) -> SqlxResult<()> {
+        sqlx::query("UPDATE orders SET price = quote WHERE id = $1 AND price IS NULL")
+            .bind(order_id)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
     #[must_use]
     pub fn permissions_checker(order_id: OrderId, session: Session) -> OrderPermissionsChecker {
         OrderPermissionsChecker {
@@ -197,10 +449,21 @@ impl OrdersTable {
     }
 }
 
+#[derive(FromRow)]
+struct IncomingOrderEventRow {
+    id: i64,
+    status: OrderStatus,
+    order_id: OrderId,
+    created_at: DateTime<Utc>,
+    order_number: String,
+    files_count: i64,
+}
+
 pub struct CompactOrdersQuery<'args> {
     qb: QueryBuilder<'args, Postgres>,
     count_qb: Option<QueryBuilder<'args, Postgres>>,
     owner_id: Option<UserId>,
+    actor_id: Option<UserId>,
     statuses: &'args [OrderStatus],
     older_than_date: Option<DateTime<FixedOffset>>,
     limit: Option<i64>,
@@ -227,6 +490,13 @@ impl<'args> CompactOrdersQuery<'args> {
         self
     }
 
+    /// Only includes orders which had a status update performed by the given user.
+    pub fn bind_actor_id(&mut self, actor_id: UserId) -> &mut Self {
+        self.actor_id = Some(actor_id);
+
+        self
+    }
+
     pub fn bind_statuses(&mut self, statuses: &'args [OrderStatus]) -> &mut Self {
         self.statuses = statuses;
 
@@ -269,6 +539,15 @@ impl<'args> CompactOrdersQuery<'args> {
                 .push_bind(owner_id);
         }
 
+        if let Some(actor_id) = self.actor_id {
+            Self::push_sep(first_bind, qb)
+                .push(
+                    "EXISTS (SELECT 1 FROM order_status_updates WHERE order_id = o.id AND actor_id = ",
+                )
+                .push_bind(actor_id)
+                .push(')');
+        }
+
         if !self.statuses.is_empty() {
             let statuses = self.statuses;
             Self::push_sep(first_bind, qb)
@@ -288,9 +567,9 @@ impl<'args> CompactOrdersQuery<'args> {
             Self::push_sep(first_bind, qb)
                 .push("(o.created_at, o.id)")
                 .push(if *reverse { " > (" } else { " < (" })
-                .push_bind(page.map(|page| page.timestamp()).unwrap_or(Utc::now()))
+                .push_bind(page.map_or(Utc::now(), |page| page.timestamp()))
                 .push(',')
-                .push_bind(page.map(|page| page.id()).unwrap_or(Uuid::max()))
+                .push_bind(page.map_or(Uuid::max(), |page| page.id()))
                 .push(')');
         }
 
@@ -376,6 +655,7 @@ impl<'args> CompactOrdersQuery<'args> {
 pub struct DetailedOrderQuery {
     id: OrderId,
     with_owner: bool,
+    with_actors: bool,
 }
 
 impl DetailedOrderQuery {
@@ -385,13 +665,20 @@ impl DetailedOrderQuery {
         self
     }
 
+    /// Includes who performed each status update in the status history.
+    pub fn with_actors(mut self, with_actors: bool) -> Self {
+        self.with_actors = with_actors;
+
+        self
+    }
+
     #[tracing::instrument(skip_all, err)]
     pub async fn fetch_one(self, conn: &mut PgConnection) -> SqlxResult<DetailedOrder> {
         let order = sqlx::query!(
             "\
             SELECT \
                 id AS \"id: OrderId\", created_at, owner_id AS \"owner_id: UserId\", order_number,\
-                status AS \"status: OrderStatus\", price, notes \
+                status AS \"status: OrderStatus\", price, quote, notes \
             FROM orders WHERE id = $1\
             ",
             self.id as OrderId,
@@ -405,17 +692,21 @@ impl DetailedOrderQuery {
 
         let status_history = sqlx::query_as(
             "\
-            SELECT created_at, status FROM order_status_updates \
+            SELECT created_at, status, reason, note, CASE WHEN $2 THEN actor_id END AS actor_id \
+            FROM order_status_updates \
             WHERE order_id = $1 ORDER BY created_at\
             ",
         )
         .bind(self.id)
+        .bind(self.with_actors)
         .fetch_all(&mut *conn)
         .await?;
 
         let files = sqlx::query_as(
             "\
-            SELECT f.id, f.object_key, f.filename, f.filetype, f.filesize, r.ranges \
+            SELECT \
+                f.id, f.object_key, f.filename, f.filetype, f.filesize, f.page_count,\
+                f.purged_at IS NOT NULL AS is_purged, r.ranges \
             FROM files AS f \
                 JOIN LATERAL (SELECT \
                     ARRAY_AGG(ROW(\
@@ -435,13 +726,14 @@ impl DetailedOrderQuery {
         let services = sqlx::query_as(
             "\
             SELECT \
-                s.type, s.binding_colour_id, s.notes, ARRAY_AGG(f.id ORDER BY f.index) AS file_ids \
+                s.type, s.binding_colour_id, s.lamination_film_id, s.notes,\
+                ARRAY_AGG(f.id ORDER BY f.index) AS file_ids \
             FROM services AS s \
                 JOIN orders AS o ON o.id = s.order_id \
                 JOIN services_files AS sf ON sf.order_id = o.id AND sf.service_id = s.id \
                 JOIN files AS f ON f.id = sf.file_id \
             WHERE s.order_id = $1 \
-                GROUP BY s.index, s.type, s.binding_colour_id, s.notes \
+                GROUP BY s.index, s.type, s.binding_colour_id, s.lamination_film_id, s.notes \
                 ORDER BY s.index\
             ",
         )
@@ -457,6 +749,7 @@ impl DetailedOrderQuery {
             order_number: order.order_number,
             status: order.status,
             price: order.price,
+            quote: order.quote,
             notes: order.notes,
             status_history,
             files,
@@ -33,26 +33,23 @@ impl PapersTable {
         let mut variant_names = Vec::with_capacity(variants_len);
         let mut is_default_vec = Vec::with_capacity(variants_len);
         let mut is_available_vec = Vec::with_capacity(variants_len);
-        let mut is_laminatable_vec = Vec::with_capacity(variants_len);
         paper.variants.iter().for_each(|paper_variant| {
             variant_names.push(paper_variant.name.as_str());
             is_default_vec.push(paper_variant.is_default);
             is_available_vec.push(paper_variant.is_available);
-            is_laminatable_vec.push(paper_variant.is_laminatable);
         });
 
         let paper_variants = sqlx::query_as(
             "\
-            INSERT INTO paper_variants (paper_id, name, is_default, is_available, is_laminatable)\
-            SELECT $1, * FROM UNNEST($2::text[], $3::bool[], $4::bool[], $5::bool[])\
-            RETURNING id, name, is_default, is_available, is_laminatable\
+            INSERT INTO paper_variants (paper_id, name, is_default, is_available)\
+            SELECT $1, * FROM UNNEST($2::text[], $3::bool[], $4::bool[])\
+            RETURNING id, name, is_default, is_available\
             ",
         )
         .bind(paper_id)
         .bind(&variant_names)
         .bind(&is_default_vec)
         .bind(&is_available_vec)
-        .bind(&is_laminatable_vec)
         .fetch_all(conn)
         .await?;
 
@@ -67,15 +64,14 @@ impl PapersTable {
     ) -> SqlxResult<PaperVariantId> {
         sqlx::query_scalar(
             "\
-            INSERT INTO paper_variants (paper_id, name, is_default, is_available, is_laminatable)\
-            VALUES ($1, $2, $3, $4, $5) RETURNING id\
+            INSERT INTO paper_variants (paper_id, name, is_default, is_available)\
+            VALUES ($1, $2, $3, $4) RETURNING id\
             ",
         )
         .bind(paper_id)
         .bind(paper_variant.name.as_str())
         .bind(paper_variant.is_default)
         .bind(paper_variant.is_available)
-        .bind(paper_variant.is_laminatable)
         .fetch_one(conn)
         .await
     }
@@ -88,7 +84,7 @@ impl PapersTable {
             FROM papers AS p \
                 JOIN LATERAL (SELECT \
                     ARRAY_AGG(ROW(\
-                        id, name, is_default, is_available, is_laminatable\
+                        id, name, is_default, is_available\
                     )::paper_variant ORDER BY is_default DESC, name) AS variants \
                 FROM paper_variants \
                 WHERE paper_id = p.id) AS v ON true \
@@ -185,7 +181,7 @@ impl PapersTable {
         sqlx::query(
             "\
             UPDATE paper_variants \
-            SET name = $3, is_default = $4, is_available = $5, is_laminatable = $6 \
+            SET name = $3, is_default = $4, is_available = $5 \
             WHERE id = $1 AND paper_id = $2",
         )
         .bind(paper_variant_id)
@@ -193,7 +189,6 @@ impl PapersTable {
         .bind(paper_variant.name.as_str())
         .bind(paper_variant.is_default)
         .bind(paper_variant.is_available)
-        .bind(paper_variant.is_laminatable)
         .execute(conn)
         .await?;
 
@@ -0,0 +1,95 @@
+use chrono::Utc;
+use sqlx::PgConnection;
+
+use crate::{SqlxResult, schemas::PricingRates};
+
+pub struct PricingTable;
+
+impl PricingTable {
+    #[tracing::instrument(skip_all, err)]
+    pub async fn fetch_rates(conn: &mut PgConnection) -> SqlxResult<PricingRates> {
+        let paper_variants = sqlx::query_as(
+            "\
+            SELECT paper_variant_id, sheet_rate, mono_side_rate, colour_side_rate \
+            FROM paper_variant_rates ORDER BY paper_variant_id\
+            ",
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let services =
+            sqlx::query_as("SELECT type, base_rate, sheet_rate FROM service_rates ORDER BY type")
+                .fetch_all(conn)
+                .await?;
+
+        Ok(PricingRates {
+            paper_variants,
+            services,
+        })
+    }
+
+    /// Inserts or replaces the given rates, leaving rates which are not mentioned untouched.
+    #[tracing::instrument(skip_all, err)]
+    pub async fn upsert_rates(conn: &mut PgConnection, rates: &PricingRates) -> SqlxResult<()> {
+        let now = Utc::now();
+
+        let variants_len = rates.paper_variants.len();
+        let mut paper_variant_ids = Vec::with_capacity(variants_len);
+        let mut sheet_rates = Vec::with_capacity(variants_len);
+        let mut mono_side_rates = Vec::with_capacity(variants_len);
+        let mut colour_side_rates = Vec::with_capacity(variants_len);
+        rates.paper_variants.iter().for_each(|rate| {
+            paper_variant_ids.push(rate.paper_variant_id);
+            sheet_rates.push(rate.sheet_rate);
+            mono_side_rates.push(rate.mono_side_rate);
+            colour_side_rates.push(rate.colour_side_rate);
+        });
+
+        sqlx::query(
+            "\
+            INSERT INTO paper_variant_rates (\
+                updated_at, paper_variant_id, sheet_rate, mono_side_rate, colour_side_rate\
+            ) SELECT $1, * FROM UNNEST($2::integer[], $3::bigint[], $4::bigint[], $5::bigint[]) \
+            ON CONFLICT (paper_variant_id) DO UPDATE SET \
+                updated_at = EXCLUDED.updated_at, sheet_rate = EXCLUDED.sheet_rate,\
+                mono_side_rate = EXCLUDED.mono_side_rate,\
+                colour_side_rate = EXCLUDED.colour_side_rate\
+            ",
+        )
+        .bind(now)
+        .bind(&paper_variant_ids)
+        .bind(&sheet_rates)
+        .bind(&mono_side_rates)
+        .bind(&colour_side_rates)
+        .execute(&mut *conn)
+        .await?;
+
+        let services_len = rates.services.len();
+        let mut types = Vec::with_capacity(services_len);
+        let mut base_rates = Vec::with_capacity(services_len);
+        let mut sheet_rates = Vec::with_capacity(services_len);
+        rates.services.iter().for_each(|rate| {
+            types.push(rate.r#type);
+            base_rates.push(rate.base_rate);
+            sheet_rates.push(rate.sheet_rate);
+        });
+
+        sqlx::query(
+            "\
+            INSERT INTO service_rates (updated_at, type, base_rate, sheet_rate) \
+            SELECT $1, * FROM UNNEST($2::service_type[], $3::bigint[], $4::bigint[]) \
+            ON CONFLICT (type) DO UPDATE SET \
+                updated_at = EXCLUDED.updated_at, base_rate = EXCLUDED.base_rate,\
+                sheet_rate = EXCLUDED.sheet_rate\
+            ",
+        )
+        .bind(now)
+        .bind(&types)
+        .bind(&base_rates)
+        .bind(&sheet_rates)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::{HashMap, HashSet};
+
+use sqlx::PgConnection;
+
+use crate::{
+    SqlxResult,
+    database::LaminationsTable,
+    schemas::{BindingColourId, BindingId, LaminationFilmId, PaperId, PaperVariantId},
+};
+
+/// A snapshot of the parts of the service catalogue which are referenced by an order, used to
+/// validate the order before it is created.
+#[derive(Debug)]
+pub struct ServiceCatalogue {
+    pub is_lamination_serviceable: bool,
+    /// `(paper_id, is_laminatable)` of each paper variant.
+    pub paper_variants: HashMap<PaperVariantId, (PaperId, bool)>,
+    /// `(binding_id, is_available)` of each binding colour, where a colour is only available if
+    /// its binding is available as well.
+    pub binding_colours: HashMap<BindingColourId, (BindingId, bool)>,
+    /// `coverable` of each binding and paper pair which are compatible with each other.
+    pub bindings_papers: HashMap<(BindingId, PaperId), bool>,
+    pub lamination_films: HashMap<LaminationFilmId, bool>,
+}
+
+pub struct ServicesTable;
+
+impl ServicesTable {
+    #[tracing::instrument(skip_all, err)]
+    pub async fn fetch_catalogue(
+        conn: &mut PgConnection,
+        paper_variant_ids: &[PaperVariantId],
+        binding_colour_ids: &[BindingColourId],
+        lamination_film_ids: &[LaminationFilmId],
+    ) -> SqlxResult<ServiceCatalogue> {
+        let is_lamination_serviceable = LaminationsTable::fetch_is_serviceable(&mut *conn).await?;
+
+        let paper_variants = sqlx::query_as(
+            "\
+            SELECT v.id, v.paper_id, l.paper_variant_id IS NOT NULL \
+            FROM paper_variants AS v \
+                LEFT JOIN lamination_paper_variants AS l ON l.paper_variant_id = v.id \
+            WHERE v.id = ANY($1)\
+            ",
+        )
+        .bind(paper_variant_ids)
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|(id, paper_id, is_laminatable)| (id, (paper_id, is_laminatable)))
+        .collect::<HashMap<_, _>>();
+
+        let binding_colours = sqlx::query_as(
+            "\
+            SELECT c.id, c.binding_id, c.is_available AND b.is_available \
+            FROM binding_colours AS c \
+                JOIN bindings AS b ON b.id = c.binding_id \
+            WHERE c.id = ANY($1)\
+            ",
+        )
+        .bind(binding_colour_ids)
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|(id, binding_id, is_available)| (id, (binding_id, is_available)))
+        .collect::<HashMap<_, _>>();
+
+        let binding_ids = binding_colours
+            .values()
+            .map(|(binding_id, _)| *binding_id)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<BindingId>>();
+        let bindings_papers = sqlx::query_as(
+            "SELECT binding_id, paper_id, coverable FROM bindings_papers WHERE binding_id = ANY($1)",
+        )
+        .bind(&binding_ids)
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|(binding_id, paper_id, coverable)| ((binding_id, paper_id), coverable))
+        .collect();
+
+        let lamination_films =
+            sqlx::query_as("SELECT id, is_available FROM lamination_films WHERE id = ANY($1)")
+                .bind(lamination_film_ids)
+                .fetch_all(conn)
+                .await?
+                .into_iter()
+                .collect();
+
+        Ok(ServiceCatalogue {
+            is_lamination_serviceable,
+            paper_variants,
+            binding_colours,
+            bindings_papers,
+            lamination_films,
+        })
+    }
+}
@@ -14,8 +14,9 @@ impl SettingsTable {
         sqlx::query_as(
             "\
             SELECT \
-                latest_orders_flushed_at, is_accepting, is_lamination_serviceable, open_time,\
-                close_time \
+                latest_orders_flushed_at, is_accepting, open_time, close_time,\
+                completed_file_retention_days, rejected_file_retention_days,\
+                cancelled_file_retention_days \
             FROM settings\
             ",
         )
@@ -46,14 +47,19 @@ impl SettingsTable {
         sqlx::query_as(
             "\
             UPDATE settings SET \
-                is_accepting = $1, is_lamination_serviceable = $2, open_time = $3, close_time = $4 \
+                is_accepting = $1, open_time = $2, close_time = $3,\
+                completed_file_retention_days = COALESCE($4, completed_file_retention_days),\
+                rejected_file_retention_days = COALESCE($5, rejected_file_retention_days),\
+                cancelled_file_retention_days = COALESCE($6, cancelled_file_retention_days) \
             RETURNING *\
             ",
         )
         .bind(settings.is_accepting)
-        .bind(settings.is_lamination_serviceable)
         .bind(settings.open_time)
         .bind(settings.close_time)
+        .bind(settings.completed_file_retention_days.map(i32::from))
+        .bind(settings.rejected_file_retention_days.map(i32::from))
+        .bind(settings.cancelled_file_retention_days.map(i32::from))
         .fetch_one(conn)
         .await
     }
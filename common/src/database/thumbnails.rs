@@ -0,0 +1,38 @@
+use sqlx::PgConnection;
+
+use crate::{SqlxResult, ThumbnailJob};
+
+pub struct ThumbnailFailuresTable;
+
+impl ThumbnailFailuresTable {
+    /// Records a thumbnail job which has exhausted all of its attempts, once for every page.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    #[tracing::instrument(skip_all, err)]
+    pub(crate) async fn create_new(
+        conn: &mut PgConnection,
+        job: &ThumbnailJob,
+        reason: &str,
+    ) -> SqlxResult<()> {
+        let pages = if job.pages.is_empty() {
+            vec![None]
+        } else {
+            job.pages.iter().map(|page| Some(*page as i32)).collect()
+        };
+
+        sqlx::query(
+            "\
+            INSERT INTO thumbnail_failures (object_key, filetype, page, attempts, reason)\
+            SELECT $1, $2, page, $4, $5 FROM UNNEST($3::integer[]) AS page\
+            ",
+        )
+        .bind(job.object_key.as_str())
+        .bind(job.filetype)
+        .bind(&pages)
+        .bind(job.attempts() as i32)
+        .bind(reason)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+}
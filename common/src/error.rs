@@ -58,7 +58,16 @@ pub enum BadRequestError {
     UnprocessableStatusUpdate,
 
     #[error("[4006] {0}")]
-    MalformedFiles(&'static str),
+    MalformedFiles(Cow<'static, str>),
+
+    #[error("[4007] The requested binding colour is currently unavailable.")]
+    UnavailableBindingColour,
+
+    #[error("[4008] Lamination is currently unavailable for the requested paper(s) or film.")]
+    UnavailableLamination,
+
+    #[error("[4009] The requested binding is incompatible with the requested paper(s).")]
+    IncompatibleBinding,
 }
 
 #[derive(Debug, Error)]
@@ -107,6 +116,9 @@ pub enum NotFoundError {
 
     #[error("[4042] The requested resource was not found.")]
     ResourceNotFound,
+
+    #[error("[4043] The requested file is no longer available.")]
+    FilePurged,
 }
 
 impl From<reqwest::Error> for AppError {
@@ -146,6 +158,15 @@ impl From<sqlx::Error> for AppError {
     }
 }
 
+impl From<std::io::Error> for AppError {
+    fn from(source: std::io::Error) -> Self {
+        match source.kind() {
+            std::io::ErrorKind::NotFound => AppError::NotFound(NotFoundError::ResourceNotFound),
+            _ => AppError::InternalServerError(anyhow!(source)),
+        }
+    }
+}
+
 impl From<tokio::task::JoinError> for AppError {
     fn from(source: tokio::task::JoinError) -> Self {
         AppError::InternalServerError(anyhow!(source))
@@ -21,15 +21,19 @@ pub(crate) mod config;
 pub(crate) mod state;
 
 pub use crate::{
-    config::Config,
+    config::{Config, StorageConfig},
     error::AppError,
-    state::{AppState, GOOGLE_SIGNING_KEYS, R2Bucket, Thumbnailer},
+    state::{
+        AppState, Bucket, Converter, GOOGLE_SIGNING_KEYS, LocalStore, ObjectStore, PresignedGet,
+        S3Store, ThumbnailJob, Thumbnailer,
+    },
 };
 
 pub type HandlerResponse<T> = Result<response::ResponseBody<T>, error::AppError>;
 pub type SqlxResult<T> = Result<T, sqlx::Error>;
 
 pub const MAX_FILE_LIMIT: usize = 10;
+pub const MAX_FILE_SIZE: u64 = 50 * 1_000_000; // 50 MB
 
 pub mod dto {
     pub use crate::{
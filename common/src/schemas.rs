@@ -4,25 +4,33 @@ mod files;
 mod ids;
 mod orders;
 mod papers;
+mod pricing;
 mod services;
 mod settings;
 mod users;
 
 pub use files::{
-    File, FileCreate, FileMetadata, FilePresignResponse, FileRange, FileUploadCreate,
-    FileUploadResponse,
+    File, FileCreate, FileMetadata, FilePagePreview, FilePresignResponse, FileRange,
+    FileRangeCreate, FileUpdate, FileUploadCreate, FileUploadResponse, MultipartUpload,
+    MultipartUploadPart, PageRange, ThumbnailJobStatus,
 };
 pub use ids::{
-    BindingColourId, BindingId, FileId, FileRangeId, OrderId, PaperId, PaperVariantId, ServiceId,
-    UserId,
+    BindingColourId, BindingId, FileId, FileRangeId, LaminationFilmId, OrderId, PaperId,
+    PaperVariantId, ServiceId, UserId,
 };
 pub use orders::{
-    ClientOrdersGlance, CompactOrder, DetailedOrder, MerchantOrdersGlance, OrderCreate,
-    OrderPriceUpdate, OrderStatusUpdate,
+    ClientOrdersGlance, CompactOrder, DetailedOrder, IncomingOrderEvent, MerchantOrdersGlance,
+    OrderCreate, OrderStatusChange, OrderStatusReason, OrderStatusSet, OrderStatusUpdate,
+    OrderStatusUpdateCreate, OrderUpdate,
 };
 pub use papers::{
     Paper, PaperCreate, PaperUpdate, PaperVariant, PaperVariantCreate, PaperWithoutVariants,
 };
-pub use services::{Binding, Service};
+pub use pricing::{PaperVariantRate, PricingRates, ServiceRate};
+pub use services::{
+    Binding, BindingColour, BindingColourCreate, BindingCreate, BindingPaper, BindingUpdate,
+    BindingWithoutColours, LaminatablePaperVariant, LaminationFilm, LaminationFilmCreate,
+    LaminationOptions, LaminationOptionsUpdate, Service,
+};
 pub use settings::{Settings, SettingsUpdate};
 pub use users::{Tel, User, UserUpdate};
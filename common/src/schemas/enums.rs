@@ -6,30 +6,139 @@ use std::{
 use serde::{Deserialize, Serialize};
 use sqlx::Type as SqlxType;
 
-#[derive(Debug, Deserialize, Clone, Copy, Serialize, SqlxType)]
+#[derive(Debug, Deserialize, Clone, Copy, Eq, PartialEq, Serialize, SqlxType)]
 #[serde(rename_all = "lowercase")]
 #[sqlx(type_name = "filetype", rename_all = "lowercase")]
 pub enum FileType {
     Pdf,
     Png,
     Jpg,
+    Docx,
+    Heic,
+    Webp,
 
     #[serde(skip)]
     #[sqlx(skip)]
-    Webp,
+    Thumbnail,
 }
 
 impl FileType {
     #[must_use]
-    pub fn to_mime(&self) -> &str {
+    pub fn to_mime(&self) -> &'static str {
         match self {
             Self::Pdf => "application/pdf",
             Self::Png => "image/png",
             Self::Jpg => "image/jpeg",
+            Self::Docx => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+            Self::Heic => "image/heic",
+            Self::Webp | Self::Thumbnail => "image/webp",
+        }
+    }
+
+    /// The file type which a file is converted into before it is printed. Files which can
+    /// already be printed as-is keep their own file type.
+    #[must_use]
+    pub fn printable(&self) -> Self {
+        match self {
+            Self::Docx => Self::Pdf,
+            Self::Heic => Self::Jpg,
+            Self::Webp => Self::Png,
+            _ => *self,
+        }
+    }
+
+    /// Whether thumbnails can be rendered from files of this type as they were uploaded, before
+    /// they are converted into their printable file type.
+    #[must_use]
+    pub fn is_thumbnailable(&self) -> bool {
+        !matches!(self, Self::Docx)
+    }
+
+    /// The number of leading bytes which [`FileType::sniff`] needs to detect any file type other
+    /// than DOCX.
+    pub const SNIFF_LENGTH: u64 = 12;
+
+    /// Detects the type of a file from its leading magic bytes. ZIP archives are only detected as
+    /// DOCX files if the whole archive is given and it holds a Word document.
+    #[must_use]
+    pub fn sniff(buffer: &[u8]) -> Option<Self> {
+        if buffer.starts_with(b"%PDF-") {
+            Some(Self::Pdf)
+        } else if buffer.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        } else if buffer.starts_with(b"\xff\xd8\xff") {
+            Some(Self::Jpg)
+        } else if buffer.starts_with(b"PK\x03\x04") {
+            is_word_document(buffer).then_some(Self::Docx)
+        } else if buffer.get(4..8) == Some(b"ftyp")
+            && buffer.get(8..12).is_some_and(|brand| {
+                [
+                    b"heic", b"heix", b"heim", b"heis", b"hevc", b"mif1", b"msf1",
+                ]
+                .iter()
+                .any(|heic_brand| heic_brand.as_slice() == brand)
+            })
+        {
+            Some(Self::Heic)
+        } else if buffer.starts_with(b"RIFF") && buffer.get(8..12) == Some(b"WEBP") {
+            Some(Self::Webp)
+        } else {
+            None
+        }
+    }
+}
+
+/// Walks the central directory of a ZIP archive, looking for the main part of a Word document.
+fn is_word_document(archive: &[u8]) -> bool {
+    const EOCD_LEN: usize = 22;
+    const CENTRAL_HEADER_LEN: usize = 46;
+
+    let read_u16 = |offset: usize| {
+        archive
+            .get(offset..offset + 2)
+            .map(|bytes| usize::from(u16::from_le_bytes([bytes[0], bytes[1]])))
+    };
+    let read_u32 = |offset: usize| {
+        archive
+            .get(offset..offset + 4)
+            .and_then(|bytes| usize::try_from(u32::from_le_bytes(bytes.try_into().ok()?)).ok())
+    };
+
+    // The end of central directory record is followed by a comment of up to 65535 bytes
+    let Some(eocd) = archive.len().checked_sub(EOCD_LEN).and_then(|last| {
+        (last.saturating_sub(usize::from(u16::MAX))..=last)
+            .rev()
+            .find(|&offset| archive[offset..].starts_with(b"PK\x05\x06"))
+    }) else {
+        return false;
+    };
+    let (Some(entries), Some(mut offset)) = (read_u16(eocd + 10), read_u32(eocd + 16)) else {
+        return false;
+    };
+
+    for _ in 0..entries {
+        if !archive
+            .get(offset..)
+            .is_some_and(|header| header.starts_with(b"PK\x01\x02"))
+        {
+            return false;
+        }
+        let (Some(name_len), Some(extra_len), Some(comment_len)) = (
+            read_u16(offset + 28),
+            read_u16(offset + 30),
+            read_u16(offset + 32),
+        ) else {
+            return false;
+        };
 
-            Self::Webp => "image/webp",
+        let name_start = offset + CENTRAL_HEADER_LEN;
+        if archive.get(name_start..name_start + name_len) == Some(b"word/document.xml") {
+            return true;
         }
+        offset = name_start + name_len + extra_len + comment_len;
     }
+
+    false
 }
 
 impl Display for FileType {
@@ -38,8 +147,11 @@ impl Display for FileType {
             Self::Pdf => write!(f, "pdf"),
             Self::Png => write!(f, "png"),
             Self::Jpg => write!(f, "jpg"),
+            Self::Docx => write!(f, "docx"),
+            Self::Heic => write!(f, "heic"),
+            Self::Webp => write!(f, "webp"),
 
-            Self::Webp => write!(f, "t.webp"),
+            Self::Thumbnail => write!(f, "t.webp"),
         }
     }
 }
@@ -52,6 +164,9 @@ impl FromStr for FileType {
             "png" => FileType::Png,
             "jpg" | "jpeg" | "jfif" => FileType::Jpg,
             "pdf" => FileType::Pdf,
+            "docx" => FileType::Docx,
+            "heic" | "heif" => FileType::Heic,
+            "webp" => FileType::Webp,
             _ => return Err("Invalid file extension"),
         })
     }
@@ -70,6 +185,45 @@ pub enum OrderStatus {
     Cancelled,
 }
 
+impl OrderStatus {
+    /// The statuses which an order in this status may be moved into by a user of the given role,
+    /// or by the server itself if no role is given. Every status update must be allowed by this
+    /// table.
+    #[must_use]
+    pub fn transitions(self, role: Option<UserRole>) -> &'static [Self] {
+        match (role, self) {
+            (Some(UserRole::Student | UserRole::Teacher), Self::Reviewing) => &[Self::Cancelled],
+            (Some(UserRole::Merchant), Self::Reviewing) => &[Self::Processing, Self::Rejected],
+            (Some(UserRole::Merchant), Self::Processing) => {
+                &[Self::Ready, Self::Reviewing, Self::Rejected]
+            }
+            (Some(UserRole::Merchant), Self::Ready) => {
+                &[Self::Completed, Self::Processing, Self::Rejected]
+            }
+            (None, Self::Reviewing) => &[Self::Rejected],
+            _ => &[],
+        }
+    }
+
+    #[must_use]
+    pub fn can_transition_to(self, next: Self, role: Option<UserRole>) -> bool {
+        self.transitions(role).contains(&next)
+    }
+
+    /// The status which follows this one when a placed order progresses normally. Orders are
+    /// created directly in [`OrderStatus::Reviewing`], so nothing follows
+    /// [`OrderStatus::Building`].
+    #[must_use]
+    pub fn next(self) -> Option<Self> {
+        match self {
+            Self::Reviewing => Some(Self::Processing),
+            Self::Processing => Some(Self::Ready),
+            Self::Ready => Some(Self::Completed),
+            Self::Building | Self::Completed | Self::Rejected | Self::Cancelled => None,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Clone, Copy, Serialize, SqlxType)]
 #[serde(rename_all = "lowercase")]
 #[sqlx(type_name = "paper_orientation", rename_all = "lowercase")]
@@ -78,7 +232,7 @@ pub enum PaperOrientation {
     Landscape,
 }
 
-#[derive(Debug, Deserialize, Clone, Copy, Serialize, SqlxType)]
+#[derive(Debug, Deserialize, Clone, Copy, Eq, PartialEq, Serialize, SqlxType)]
 #[serde(rename_all = "camelCase")]
 #[sqlx(type_name = "service_type", rename_all = "snake_case")]
 pub enum ServiceType {
@@ -95,3 +249,136 @@ pub enum UserRole {
     Teacher,
     Merchant,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATUSES: [OrderStatus; 7] = [
+        OrderStatus::Building,
+        OrderStatus::Reviewing,
+        OrderStatus::Processing,
+        OrderStatus::Ready,
+        OrderStatus::Completed,
+        OrderStatus::Rejected,
+        OrderStatus::Cancelled,
+    ];
+
+    type Transitions = (OrderStatus, &'static [OrderStatus]);
+
+    /// Builds a ZIP archive of empty, stored entries with the given names.
+    fn zip(names: &[&str]) -> Vec<u8> {
+        let mut archive = Vec::new();
+        let mut central_directory = Vec::new();
+        for name in names {
+            let offset = u32::try_from(archive.len()).unwrap();
+            let name_len = u16::try_from(name.len()).unwrap();
+
+            archive.extend(b"PK\x03\x04");
+            archive.extend([0; 22]);
+            archive.extend(name_len.to_le_bytes());
+            archive.extend([0; 2]);
+            archive.extend(name.as_bytes());
+
+            central_directory.extend(b"PK\x01\x02");
+            central_directory.extend([0; 24]);
+            central_directory.extend(name_len.to_le_bytes());
+            central_directory.extend([0; 12]);
+            central_directory.extend(offset.to_le_bytes());
+            central_directory.extend(name.as_bytes());
+        }
+
+        let entries = u16::try_from(names.len()).unwrap();
+        let size = u32::try_from(central_directory.len()).unwrap();
+        let offset = u32::try_from(archive.len()).unwrap();
+        archive.extend(central_directory);
+        archive.extend(b"PK\x05\x06");
+        archive.extend([0; 4]);
+        archive.extend(entries.to_le_bytes());
+        archive.extend(entries.to_le_bytes());
+        archive.extend(size.to_le_bytes());
+        archive.extend(offset.to_le_bytes());
+        archive.extend([0; 2]);
+
+        archive
+    }
+
+    #[test]
+    fn sniffs_magic_bytes() {
+        assert_eq!(FileType::sniff(b"%PDF-1.7\n"), Some(FileType::Pdf));
+        assert_eq!(FileType::sniff(b"\x89PNG\r\n\x1a\n"), Some(FileType::Png));
+        assert_eq!(FileType::sniff(b"\xff\xd8\xff\xe0"), Some(FileType::Jpg));
+        assert_eq!(FileType::sniff(b"\0\0\0\x18ftypheic"), Some(FileType::Heic));
+        assert_eq!(FileType::sniff(b"RIFF\0\0\0\0WEBP"), Some(FileType::Webp));
+        assert_eq!(FileType::sniff(b"GIF89a"), None);
+        assert_eq!(FileType::sniff(b""), None);
+    }
+
+    #[test]
+    fn sniffs_only_word_documents_as_docx() {
+        assert_eq!(
+            FileType::sniff(&zip(&["[Content_Types].xml", "word/document.xml"])),
+            Some(FileType::Docx)
+        );
+        assert_eq!(
+            FileType::sniff(&zip(&["[Content_Types].xml", "xl/workbook.xml"])),
+            None
+        );
+        assert_eq!(FileType::sniff(&zip(&[])), None);
+
+        let archive = zip(&["word/document.xml"]);
+        assert_eq!(FileType::sniff(&archive[..12]), None);
+        assert_eq!(FileType::sniff(&archive[..archive.len() - 1]), None);
+    }
+
+    #[test]
+    fn allows_only_the_transitions_of_each_role() {
+        use OrderStatus::{Cancelled, Completed, Processing, Ready, Rejected, Reviewing};
+
+        let table: [(Option<UserRole>, &[Transitions]); 4] = [
+            (Some(UserRole::Student), &[(Reviewing, &[Cancelled])]),
+            (Some(UserRole::Teacher), &[(Reviewing, &[Cancelled])]),
+            (
+                Some(UserRole::Merchant),
+                &[
+                    (Reviewing, &[Processing, Rejected]),
+                    (Processing, &[Ready, Reviewing, Rejected]),
+                    (Ready, &[Completed, Processing, Rejected]),
+                ],
+            ),
+            (None, &[(Reviewing, &[Rejected])]),
+        ];
+
+        for (role, transitions) in table {
+            for status in STATUSES {
+                let expected = transitions
+                    .iter()
+                    .find(|(from, _)| *from == status)
+                    .map_or(&[][..], |(_, to)| to);
+                assert_eq!(
+                    status.transitions(role),
+                    expected,
+                    "{role:?} from {status:?}"
+                );
+                for next in STATUSES {
+                    assert_eq!(
+                        status.can_transition_to(next, role),
+                        expected.contains(&next),
+                        "{role:?} from {status:?} to {next:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn progresses_only_through_transitions_of_the_merchant() {
+        for status in STATUSES {
+            if let Some(next) = status.next() {
+                assert!(status.can_transition_to(next, Some(UserRole::Merchant)));
+            }
+        }
+        assert_eq!(OrderStatus::Building.next(), None);
+        assert_eq!(OrderStatus::Ready.next(), Some(OrderStatus::Completed));
+    }
+}
@@ -1,7 +1,12 @@
-use std::sync::Arc;
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+    sync::Arc,
+};
 
 use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, Type as SqlxType};
+use uuid::Uuid;
 
 use crate::schemas::{
     FileId, PaperVariantId,
@@ -16,8 +21,11 @@ pub struct File {
     pub(crate) filename: String,
     pub(crate) filetype: FileType,
     pub(crate) filesize: i64,
+    pub(crate) page_count: Option<i32>,
     #[serde(skip_serializing)]
     pub(crate) object_key: String,
+    /// Whether the file has been deleted from the bucket by the retention policy.
+    pub(crate) is_purged: bool,
     pub(crate) ranges: Vec<FileRange>,
 }
 
@@ -40,12 +48,17 @@ pub struct FileMetadata {
     pub object_key: String,
     pub filename: String,
     pub filetype: FileType,
+    pub page_count: Option<i32>,
+    pub is_purged: bool,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct FileUploadCreate {
     pub filetype: FileType,
     pub filesize: u64,
+    /// Uploads the file in parts which can be retried individually instead of all at once.
+    #[serde(default)]
+    pub multipart: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -53,7 +66,26 @@ pub struct FileUploadCreate {
 pub struct FileUploadResponse {
     pub id: FileId,
     pub object_key: String,
-    pub upload_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upload_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multipart: Option<MultipartUpload>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MultipartUpload {
+    pub part_size: u64,
+    /// The parts which have already been uploaded and need not be uploaded again.
+    pub uploaded_parts: Vec<u16>,
+    pub parts: Vec<MultipartUploadPart>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MultipartUploadPart {
+    pub part_number: u16,
+    pub url: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -64,7 +96,15 @@ pub struct FileCreate {
     pub ranges: Vec<FileRangeCreate>,
 }
 
-#[derive(Debug, Deserialize)]
+/// Replaces every page range of a file in an order which is still being reviewed.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileUpdate {
+    pub id: FileId,
+    pub ranges: Vec<FileRangeCreate>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FileRangeCreate {
     pub range: Option<String>,
@@ -75,8 +115,258 @@ pub struct FileRangeCreate {
     pub is_double_sided: bool,
 }
 
+impl From<FileRangeCreate> for FileRange {
+    fn from(file_range: FileRangeCreate) -> Self {
+        Self {
+            id: Uuid::new_v4().into(),
+            range: file_range.range,
+            copies: file_range.copies,
+            paper_variant_id: Some(file_range.paper_variant_id),
+            paper_orientation: file_range.paper_orientation,
+            is_colour: file_range.is_colour,
+            is_double_sided: file_range.is_double_sided,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum ThumbnailJobStatus {
+    /// The file has to be converted into its printable file type before its thumbnail can be
+    /// rendered, which happens once its order is placed.
+    AwaitingConversion,
+    Queued,
+    Processing,
+    Done,
+    Failed {
+        reason: String,
+    },
+}
+
+#[derive(Debug, Serialize)]
+pub struct FilePagePreview {
+    pub page: u32,
+    pub url: Option<Arc<str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job: Option<ThumbnailJobStatus>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct FilePresignResponse {
     pub id: FileId,
-    pub url: Arc<str>,
+    /// Missing if the file is no longer available.
+    pub url: Option<Arc<str>>,
+}
+
+/// A set of pages of a file, written as comma-separated pages and inclusive spans of pages, such as
+/// `1-3,5,8-`, where a span without an end runs until the last page of the file.
+///
+/// Parsing a page range normalises it, so that its spans are sorted and overlapping or adjacent
+/// spans are merged together. Displaying it gives back its canonical form.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PageRange(Vec<PageSpan>);
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct PageSpan {
+    start: u32,
+    end: Option<u32>,
+}
+
+impl PageRange {
+    #[must_use]
+    pub fn overlaps(&self, other: &Self) -> bool {
+        self.0.iter().any(|span| {
+            other.0.iter().any(|other_span| {
+                span.start <= other_span.end.unwrap_or(u32::MAX)
+                    && other_span.start <= span.end.unwrap_or(u32::MAX)
+            })
+        })
+    }
+
+    /// Checks whether every page of the page range exists in a file with the given page count.
+    #[must_use]
+    pub fn fits_within(&self, page_count: u32) -> bool {
+        self.0
+            .iter()
+            .all(|span| span.start <= page_count && span.end.is_none_or(|end| end <= page_count))
+    }
+
+    /// Counts the pages covered by the page range. Returns `None` if the page range is open-ended
+    /// and the page count of the file is unknown, or if the count does not fit in a `u32`.
+    #[must_use]
+    pub fn count_pages(&self, page_count: Option<u32>) -> Option<u32> {
+        self.0.iter().try_fold(0u32, |pages, span| {
+            let end = span.end.or(page_count)?;
+
+            // Pages start from 1, so a span never covers more than `u32::MAX` pages
+            pages.checked_add(end.checked_sub(span.start).map_or(0, |pages| pages + 1))
+        })
+    }
+}
+
+impl Display for PageRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, span) in self.0.iter().enumerate() {
+            if index != 0 {
+                write!(f, ",")?;
+            }
+
+            match span.end {
+                Some(end) if end == span.start => write!(f, "{}", span.start)?,
+                Some(end) => write!(f, "{}-{end}", span.start)?,
+                None => write!(f, "{}-", span.start)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for PageRange {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_page = |page: &str| match page.trim().parse::<u32>() {
+            Ok(page) if page != 0 => Ok(page),
+            _ => Err("Invalid page number"),
+        };
+
+        let mut spans = s
+            .split(',')
+            .map(|part| {
+                Ok(match part.split_once('-') {
+                    Some((start, end)) if end.trim().is_empty() => PageSpan {
+                        start: parse_page(start)?,
+                        end: None,
+                    },
+                    Some((start, end)) => {
+                        let (start, end) = (parse_page(start)?, parse_page(end)?);
+                        if start > end {
+                            return Err("Invalid page span");
+                        }
+
+                        PageSpan {
+                            start,
+                            end: Some(end),
+                        }
+                    }
+                    None => {
+                        let page = parse_page(part)?;
+
+                        PageSpan {
+                            start: page,
+                            end: Some(page),
+                        }
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        spans.sort_unstable_by_key(|span| span.start);
+        let mut merged: Vec<PageSpan> = Vec::with_capacity(spans.len());
+        for span in spans {
+            match merged.last_mut() {
+                Some(last)
+                    if last
+                        .end
+                        .is_none_or(|end| span.start <= end.saturating_add(1)) =>
+                {
+                    last.end = last
+                        .end
+                        .zip(span.end)
+                        .map(|(end, span_end)| end.max(span_end));
+                }
+                _ => merged.push(span),
+            }
+        }
+
+        Ok(Self(merged))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PageRange;
+
+    fn parse(s: &str) -> PageRange {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_pages_and_spans() {
+        assert_eq!(parse("1-3,5,8-").to_string(), "1-3,5,8-");
+        assert_eq!(parse(" 2 - 4 , 7 ").to_string(), "2-4,7");
+        assert_eq!(parse("4-4").to_string(), "4");
+    }
+
+    #[test]
+    fn rejects_malformed_page_ranges() {
+        for s in [
+            "",
+            "0",
+            "0-2",
+            "3-1",
+            "a",
+            "1-a",
+            "-3",
+            "1--2",
+            "1,,2",
+            "4294967296",
+        ] {
+            assert!(s.parse::<PageRange>().is_err(), "`{s}` should be rejected");
+        }
+    }
+
+    #[test]
+    fn displays_its_canonical_form_which_parses_back() {
+        for s in ["1", "1-3", "2-", "1-3,5,8-", "1,3,5"] {
+            let page_range = parse(s);
+            assert_eq!(page_range.to_string(), s);
+            assert_eq!(parse(&page_range.to_string()), page_range);
+        }
+    }
+
+    #[test]
+    fn normalises_unordered_overlapping_and_adjacent_spans() {
+        assert_eq!(parse("5,1-2").to_string(), "1-2,5");
+        assert_eq!(parse("1-3,2-5").to_string(), "1-5");
+        assert_eq!(parse("1-3,4,6").to_string(), "1-4,6");
+        assert_eq!(parse("3,1-").to_string(), "1-");
+        assert_eq!(parse("8-,2-9").to_string(), "2-");
+        assert_eq!(parse("2,2,2").to_string(), "2");
+    }
+
+    #[test]
+    fn detects_overlapping_page_ranges() {
+        assert!(parse("1-3").overlaps(&parse("3-5")));
+        assert!(parse("1,10-").overlaps(&parse("20")));
+        assert!(parse("4-").overlaps(&parse("1-2,6")));
+        assert!(!parse("1-3").overlaps(&parse("4-5")));
+        assert!(!parse("1,3,5").overlaps(&parse("2,4,6-")));
+    }
+
+    #[test]
+    fn fits_within_the_page_count() {
+        assert!(parse("1-3,5").fits_within(5));
+        assert!(parse("5-").fits_within(5));
+        assert!(!parse("1-6").fits_within(5));
+        assert!(!parse("6-").fits_within(5));
+    }
+
+    #[test]
+    fn counts_pages() {
+        assert_eq!(parse("1-3,5").count_pages(None), Some(4));
+        assert_eq!(parse("2,4-").count_pages(Some(10)), Some(8));
+        assert_eq!(parse("4-").count_pages(None), None);
+    }
+
+    #[test]
+    fn counts_pages_without_overflowing() {
+        assert_eq!(parse("1-4294967295").count_pages(None), Some(u32::MAX));
+        assert_eq!(
+            parse("1,3-4294967295").count_pages(None),
+            Some(u32::MAX - 1)
+        );
+        assert_eq!(parse("4294967295-").count_pages(Some(u32::MAX)), Some(1));
+    }
 }
@@ -27,6 +27,12 @@ pub struct FileId(Uuid);
 #[sqlx(transparent)]
 pub struct FileRangeId(Uuid);
 
+#[derive(Clone, Copy, Debug, Deserialize, Eq, From, Hash, PartialEq, Serialize, SqlxType)]
+#[repr(transparent)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct LaminationFilmId(i32);
+
 #[derive(Clone, Copy, Debug, Deserialize, Eq, From, Hash, PartialEq, Serialize, SqlxType)]
 #[repr(transparent)]
 #[serde(transparent)]
@@ -2,7 +2,9 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
-use crate::schemas::{File, FileCreate, OrderId, Service, User, UserId, enums::OrderStatus};
+use crate::schemas::{
+    File, FileCreate, FileUpdate, OrderId, Service, User, UserId, enums::OrderStatus,
+};
 
 #[derive(Debug, Serialize)]
 pub struct ClientOrdersGlance {
@@ -18,7 +20,7 @@ pub struct MerchantOrdersGlance {
     pub finished: Vec<CompactOrder>,
 }
 
-#[derive(Debug, FromRow, Serialize)]
+#[derive(Clone, Debug, Deserialize, FromRow, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CompactOrder {
     pub id: OrderId,
@@ -40,6 +42,7 @@ pub struct DetailedOrder {
     pub order_number: String,
     pub status: OrderStatus,
     pub price: Option<i64>,
+    pub quote: Option<i64>,
     pub notes: Option<String>,
     pub status_history: Vec<OrderStatusUpdate>,
     pub files: Vec<File>,
@@ -47,15 +50,108 @@ pub struct DetailedOrder {
 }
 
 #[derive(Debug, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct OrderStatusUpdate {
     #[sqlx(rename = "created_at")]
     pub(crate) timestamp: DateTime<Utc>,
     pub(crate) status: OrderStatus,
+    pub(crate) reason: Option<String>,
+    pub(crate) note: Option<String>,
+    /// The user who performed the status update, which is only shown to merchants. Missing for
+    /// updates performed by the system.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) actor_id: Option<UserId>,
 }
 
+/// Explains why an order was moved into a status, such as why it was rejected.
+#[derive(Debug, Default, Deserialize)]
+pub struct OrderStatusReason {
+    pub reason: Option<String>,
+    pub note: Option<String>,
+}
+
+impl OrderStatusReason {
+    /// The reason given to orders which are rejected by the daily flush.
+    #[must_use]
+    pub fn not_reviewed_before_close() -> Self {
+        Self {
+            reason: Some(String::from("Not reviewed before close")),
+            note: None,
+        }
+    }
+
+    /// Trims the reason and the note, discarding them if they are empty.
+    #[must_use]
+    pub fn normalise(self) -> Self {
+        let normalise = |text: Option<String>| {
+            text.map(|text| text.trim().to_string())
+                .filter(|text| !text.is_empty())
+        };
+
+        Self {
+            reason: normalise(self.reason),
+            note: normalise(self.note),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderStatusChange {
+    pub order_id: OrderId,
+    #[serde(skip_serializing)]
+    pub owner_id: UserId,
+    pub status: OrderStatus,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum IncomingOrderEvent {
+    Added {
+        id: i64,
+        order: CompactOrder,
+    },
+    #[serde(rename_all = "camelCase")]
+    Removed {
+        id: i64,
+        order_id: OrderId,
+        status: OrderStatus,
+    },
+}
+
+impl IncomingOrderEvent {
+    #[must_use]
+    pub fn id(&self) -> i64 {
+        match self {
+            Self::Added { id, .. } | Self::Removed { id, .. } => *id,
+        }
+    }
+}
+
+/// Moves an order into a specific status, including back into an earlier one.
 #[derive(Debug, Deserialize)]
-pub struct OrderPriceUpdate {
-    pub price: i64,
+pub struct OrderStatusSet {
+    pub status: OrderStatus,
+    #[serde(flatten)]
+    pub reason: OrderStatusReason,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct OrderStatusUpdateCreate {
+    /// Overrides the quoted price when an order is accepted.
+    pub price: Option<i64>,
+    #[serde(flatten)]
+    pub reason: OrderStatusReason,
+}
+
+/// Changes the options of an order which is still being reviewed. Missing fields are left
+/// unchanged, and an empty `notes` removes the notes.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct OrderUpdate {
+    pub notes: Option<String>,
+    pub files: Option<Vec<FileUpdate>>,
+    pub services: Option<Vec<Service>>,
 }
 
 #[derive(Debug, Deserialize)]
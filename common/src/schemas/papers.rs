@@ -51,7 +51,6 @@ pub struct PaperVariant {
     pub name: String,
     pub is_default: bool,
     pub is_available: bool,
-    pub is_laminatable: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -60,5 +59,4 @@ pub struct PaperVariantCreate {
     pub name: String,
     pub is_default: bool,
     pub is_available: bool,
-    pub is_laminatable: bool,
 }
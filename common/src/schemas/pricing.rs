@@ -0,0 +1,248 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::schemas::{File, PageRange, PaperVariantId, Service, enums::ServiceType};
+
+/// Every rate is in the smallest unit of the currency, like the price of an order.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PricingRates {
+    pub paper_variants: Vec<PaperVariantRate>,
+    pub services: Vec<ServiceRate>,
+}
+
+#[derive(Debug, Deserialize, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaperVariantRate {
+    pub paper_variant_id: PaperVariantId,
+    pub sheet_rate: i64,
+    pub mono_side_rate: i64,
+    pub colour_side_rate: i64,
+}
+
+#[derive(Debug, Deserialize, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceRate {
+    pub r#type: ServiceType,
+    pub base_rate: i64,
+    pub sheet_rate: i64,
+}
+
+impl PricingRates {
+    /// Computes the quoted price of an order. Each file range costs a sheet rate for every sheet
+    /// and a side rate for every printed page, while each service costs a base rate plus a sheet
+    /// rate for every sheet of the files it is applied to.
+    ///
+    /// Returns `None` if the order cannot be quoted, such as when the page count of a file is
+    /// unknown or when a rate is missing for any of the requested paper variants or services.
+    #[must_use]
+    pub fn quote(&self, files: &[File], services: &[Service]) -> Option<i64> {
+        let mut quote = 0;
+        let mut files_sheets = Vec::with_capacity(files.len());
+        for file in files {
+            let mut file_sheets = 0;
+            for file_range in &file.ranges {
+                let rate = self
+                    .paper_variants
+                    .iter()
+                    .find(|rate| Some(rate.paper_variant_id) == file_range.paper_variant_id)?;
+                let page_count = file.page_count.and_then(|count| u32::try_from(count).ok());
+                let pages = match file_range.range.as_deref() {
+                    Some(range) => range.parse::<PageRange>().ok()?.count_pages(page_count)?,
+                    None => page_count?,
+                };
+                let pages = i64::from(pages);
+                let sheets = if file_range.is_double_sided {
+                    (pages + 1) / 2
+                } else {
+                    pages
+                };
+                let side_rate = if file_range.is_colour {
+                    rate.colour_side_rate
+                } else {
+                    rate.mono_side_rate
+                };
+
+                let copies = i64::from(file_range.copies);
+                quote += copies * (sheets * rate.sheet_rate + pages * side_rate);
+                file_sheets += copies * sheets;
+            }
+            files_sheets.push((file.id, file_sheets));
+        }
+
+        for service in services {
+            let rate = self
+                .services
+                .iter()
+                .find(|rate| rate.r#type == service.r#type)?;
+            let sheets = files_sheets
+                .iter()
+                .filter(|(file_id, _)| service.file_ids.contains(file_id))
+                .map(|(_, sheets)| sheets)
+                .sum::<i64>();
+
+            quote += rate.base_rate + sheets * rate.sheet_rate;
+        }
+
+        Some(quote)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::{PaperVariantRate, PricingRates, ServiceRate};
+    use crate::schemas::{
+        File, FileId, FileRange, FileRangeId, PaperVariantId, Service,
+        enums::{FileType, PaperOrientation, ServiceType},
+    };
+
+    fn rates() -> PricingRates {
+        PricingRates {
+            paper_variants: vec![PaperVariantRate {
+                paper_variant_id: PaperVariantId::from(1),
+                sheet_rate: 10,
+                mono_side_rate: 2,
+                colour_side_rate: 5,
+            }],
+            services: vec![
+                ServiceRate {
+                    r#type: ServiceType::Binding,
+                    base_rate: 100,
+                    sheet_rate: 1,
+                },
+                ServiceRate {
+                    r#type: ServiceType::Laminate,
+                    base_rate: 50,
+                    sheet_rate: 3,
+                },
+            ],
+        }
+    }
+
+    fn file(id: u128, page_count: Option<i32>, ranges: Vec<FileRange>) -> File {
+        File {
+            id: FileId::from(Uuid::from_u128(id)),
+            filename: String::from("file.pdf"),
+            filetype: FileType::Pdf,
+            filesize: 0,
+            page_count,
+            object_key: String::new(),
+            is_purged: false,
+            ranges,
+        }
+    }
+
+    fn range(
+        range: Option<&str>,
+        copies: i32,
+        is_colour: bool,
+        is_double_sided: bool,
+    ) -> FileRange {
+        FileRange {
+            id: FileRangeId::from(Uuid::nil()),
+            range: range.map(String::from),
+            copies,
+            paper_variant_id: Some(PaperVariantId::from(1)),
+            paper_orientation: PaperOrientation::Portrait,
+            is_colour,
+            is_double_sided,
+        }
+    }
+
+    fn service(r#type: ServiceType, file_ids: &[u128]) -> Service {
+        Service {
+            r#type,
+            binding_colour_id: None,
+            lamination_film_id: None,
+            notes: None,
+            file_ids: file_ids
+                .iter()
+                .map(|id| FileId::from(Uuid::from_u128(*id)))
+                .collect(),
+        }
+    }
+
+    fn quote(page_count: Option<i32>, file_range: FileRange) -> Option<i64> {
+        rates().quote(&[file(1, page_count, vec![file_range])], &[])
+    }
+
+    #[test]
+    fn counts_sheets_from_page_ranges() {
+        assert_eq!(
+            quote(Some(10), range(Some("1-3,5"), 1, false, false)),
+            Some(48)
+        );
+        assert_eq!(
+            quote(Some(10), range(Some("8-"), 1, false, false)),
+            Some(36)
+        );
+        assert_eq!(quote(Some(10), range(None, 1, false, false)), Some(120));
+    }
+
+    #[test]
+    fn halves_sheets_when_double_sided() {
+        assert_eq!(quote(Some(5), range(None, 1, false, false)), Some(60));
+        assert_eq!(quote(Some(5), range(None, 1, false, true)), Some(40));
+        assert_eq!(quote(Some(4), range(None, 1, false, true)), Some(28));
+    }
+
+    #[test]
+    fn charges_the_side_rate_of_the_colour_mode() {
+        assert_eq!(quote(Some(4), range(None, 1, false, false)), Some(48));
+        assert_eq!(quote(Some(4), range(None, 1, true, false)), Some(60));
+    }
+
+    #[test]
+    fn multiplies_by_copies() {
+        assert_eq!(quote(Some(4), range(None, 3, false, false)), Some(144));
+        assert_eq!(quote(Some(4), range(None, 3, true, true)), Some(120));
+    }
+
+    #[test]
+    fn charges_services_for_the_sheets_of_their_files() {
+        let files = [
+            file(1, Some(4), vec![range(None, 2, false, false)]),
+            file(2, Some(6), vec![range(None, 1, false, true)]),
+        ];
+        assert_eq!(rates().quote(&files, &[]), Some(138));
+        assert_eq!(
+            rates().quote(&files, &[service(ServiceType::Binding, &[1])]),
+            Some(138 + 100 + 8)
+        );
+        assert_eq!(
+            rates().quote(
+                &files,
+                &[
+                    service(ServiceType::Binding, &[1]),
+                    service(ServiceType::Laminate, &[1, 2]),
+                ]
+            ),
+            Some(138 + 100 + 8 + 50 + 11 * 3)
+        );
+    }
+
+    #[test]
+    fn cannot_quote_without_page_counts_or_rates() {
+        assert_eq!(quote(None, range(None, 1, false, false)), None);
+        assert_eq!(quote(None, range(Some("2-"), 1, false, false)), None);
+        assert_eq!(
+            quote(
+                Some(4),
+                FileRange {
+                    paper_variant_id: Some(PaperVariantId::from(2)),
+                    ..range(None, 1, false, false)
+                }
+            ),
+            None
+        );
+        assert_eq!(
+            rates().quote(
+                &[file(1, Some(4), vec![range(None, 1, false, false)])],
+                &[service(ServiceType::BindingWithCover, &[1])]
+            ),
+            None
+        );
+    }
+}
@@ -1,30 +1,116 @@
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::{FromRow, Type as SqlxType};
 
-use crate::schemas::{BindingColourId, BindingId, FileId, enums::ServiceType};
+use crate::schemas::{
+    BindingColourId, BindingId, FileId, LaminationFilmId, PaperId, PaperVariantId,
+    enums::ServiceType,
+};
 
 #[derive(Debug, Deserialize, FromRow, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Service {
     pub(crate) r#type: ServiceType,
     pub(crate) binding_colour_id: Option<BindingColourId>,
+    pub(crate) lamination_film_id: Option<LaminationFilmId>,
     pub(crate) notes: Option<String>,
     pub(crate) file_ids: Vec<FileId>,
 }
 
-#[derive(Debug, Deserialize, FromRow, Serialize)]
+#[derive(Debug, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BindingWithoutColours {
+    pub id: BindingId,
+    pub name: String,
+    pub is_available: bool,
+}
+
+#[derive(Debug, FromRow, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Binding {
-    id: BindingId,
-    name: String,
-    is_available: bool,
-    colours: Vec<BindingColour>,
+    pub id: BindingId,
+    pub name: String,
+    pub is_available: bool,
+    pub colours: Vec<BindingColour>,
+    pub papers: Vec<BindingPaper>,
 }
 
-#[derive(Debug, Deserialize, FromRow, Serialize)]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BindingCreate {
+    pub name: String,
+    pub is_available: bool,
+    pub colours: Vec<BindingColourCreate>,
+    pub papers: Vec<BindingPaper>,
+}
+
+#[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
+pub struct BindingUpdate {
+    pub name: String,
+    pub is_available: bool,
+}
+
+#[derive(Debug, FromRow, Serialize, SqlxType)]
+#[serde(rename_all = "camelCase")]
+#[sqlx(type_name = "binding_colour")]
 pub struct BindingColour {
-    id: BindingColourId,
-    colour: String,
-    is_available: bool,
+    pub id: BindingColourId,
+    pub colour: String,
+    pub is_available: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BindingColourCreate {
+    pub colour: String,
+    pub is_available: bool,
+}
+
+#[derive(Debug, Deserialize, FromRow, Serialize, SqlxType)]
+#[serde(rename_all = "camelCase")]
+#[sqlx(type_name = "binding_paper")]
+pub struct BindingPaper {
+    pub paper_id: PaperId,
+    pub coverable: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LaminationOptions {
+    pub is_serviceable: bool,
+    pub films: Vec<LaminationFilm>,
+    pub paper_variants: Vec<LaminatablePaperVariant>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LaminationOptionsUpdate {
+    pub is_serviceable: bool,
+    pub laminatable_paper_variant_ids: Vec<PaperVariantId>,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LaminationFilm {
+    pub id: LaminationFilmId,
+    pub name: String,
+    pub is_available: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LaminationFilmCreate {
+    pub name: String,
+    pub is_available: bool,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LaminatablePaperVariant {
+    pub id: PaperVariantId,
+    pub paper_id: PaperId,
+    pub paper_name: String,
+    pub name: String,
+    pub is_available: bool,
+    pub is_laminatable: bool,
 }
@@ -7,16 +7,22 @@ use sqlx::FromRow;
 pub struct Settings {
     pub(crate) latest_orders_flushed_at: Option<DateTime<Utc>>,
     pub(crate) is_accepting: bool,
-    pub(crate) is_lamination_serviceable: bool,
     pub(crate) open_time: NaiveTime,
     pub(crate) close_time: NaiveTime,
+    pub(crate) completed_file_retention_days: i32,
+    pub(crate) rejected_file_retention_days: i32,
+    pub(crate) cancelled_file_retention_days: i32,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SettingsUpdate {
     pub is_accepting: bool,
-    pub is_lamination_serviceable: bool,
     pub open_time: NaiveTime,
     pub close_time: NaiveTime,
+    /// The number of days for which the files of an order are kept after it is completed. The
+    /// retention periods are left unchanged when they are omitted.
+    pub completed_file_retention_days: Option<u16>,
+    pub rejected_file_retention_days: Option<u16>,
+    pub cancelled_file_retention_days: Option<u16>,
 }
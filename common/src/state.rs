@@ -13,13 +13,17 @@ use tokio::sync::Mutex;
 use crate::{Config, auth::SessionStore};
 
 mod bucket;
+mod converter;
 mod drafts;
+mod events;
 mod thumbnailer;
 
-pub use bucket::R2Bucket;
-pub(super) use drafts::DraftOrderStore;
-pub use thumbnailer::Thumbnailer;
+pub use bucket::{Bucket, LocalStore, ObjectStore, PresignedGet, S3Store};
+pub use converter::Converter;
+pub(super) use drafts::{DraftOrderStore, MAX_FILE_RANGES};
+pub(super) use events::{INCOMING_ORDERS_CHANNEL, ORDER_STATUS_CHANGES_CHANNEL, OrderEvents};
 pub(crate) use thumbnailer::vips_version_check;
+pub use thumbnailer::{ThumbnailJob, Thumbnailer};
 
 pub static GOOGLE_SIGNING_KEYS: LazyLock<ArcSwap<JwkSet>> =
     LazyLock::new(|| ArcSwap::from_pointee(JwkSet { keys: Vec::new() }));
@@ -31,10 +35,12 @@ pub struct AppState {
     pub config: Arc<Config>,
     pub pool: PgPool,
     pub http: ReqwestClient,
-    pub bucket: R2Bucket,
+    pub bucket: Bucket,
+    pub converter: Converter,
     pub sessions: SessionStore,
     pub oauth_states: Arc<Mutex<OAuthStates>>,
     pub draft_orders: DraftOrderStore,
+    pub order_events: OrderEvents,
     pub thumbnailer: Thumbnailer,
 }
 
@@ -45,7 +51,7 @@ impl AppState {
         config: Arc<Config>,
         pool: PgPool,
         http: ReqwestClient,
-        bucket: R2Bucket,
+        bucket: Bucket,
         thumbnailer: Thumbnailer,
     ) -> Self {
         AppState {
@@ -53,9 +59,11 @@ impl AppState {
             pool,
             http,
             bucket,
+            converter: Converter::new(config.office_converter()),
             sessions: SessionStore::new(config.secret().as_bytes(), config.session_expiry_time()),
             oauth_states: Arc::new(Mutex::new(Vec::new())),
             draft_orders: DraftOrderStore::new(),
+            order_events: OrderEvents::new(),
             thumbnailer,
         }
     }
@@ -64,4 +72,9 @@ impl AppState {
     pub async fn load_sessions(&self) -> AnyhowResult<()> {
         self.sessions.load(self.pool.clone()).await
     }
+
+    #[tracing::instrument(skip_all, err)]
+    pub async fn load_draft_orders(&self) -> AnyhowResult<()> {
+        self.draft_orders.load(self.pool.clone()).await
+    }
 }
@@ -1,84 +1,253 @@
 use std::{
+    collections::HashSet,
     fmt::{Debug, Display},
     sync::Arc,
     time::Duration as StdDuration,
 };
 
-use anyhow::Result as AnyhowResult;
 use bytes::Bytes;
 use chrono::{DateTime, TimeDelta, Utc};
-use http::header::CONTENT_TYPE;
-use reqwest::Client as ReqwestClient;
-use rusty_s3::{Bucket, Credentials, S3Action as _, UrlStyle, actions::ObjectIdentifier};
+use futures::future::{self, BoxFuture};
 use scc::HashIndex;
 
 use crate::{
-    AppError,
+    AppError, MAX_FILE_SIZE,
     error::{BadRequestError, NotFoundError},
-    schemas::enums::FileType,
+    schemas::{MultipartUpload, MultipartUploadPart, enums::FileType},
 };
 
-const DEFAULT_SIGN_DURATION: StdDuration = StdDuration::from_secs(60);
+mod local;
+mod s3;
+
+pub use local::LocalStore;
+pub use s3::S3Store;
+
+/// The size of every part of a multipart upload except for the last, which must be at least 5 MiB
+/// for S3-compatible services.
+pub const MULTIPART_PART_SIZE: u64 = 8 * 1024 * 1024;
+/// How long presigned upload URLs stay valid for, both for whole files and for single parts.
+const UPLOAD_EXPIRY: StdDuration = StdDuration::from_secs(15 * 60);
+
+type PresignCache = HashIndex<String, (Arc<str>, DateTime<Utc>)>; /* (url, expiry) */
+
+/// A storage backend which holds the uploaded files and their thumbnails. Objects are addressed by
+/// their names relative to the root of the store, e.g. `0123abcd.pdf`. Reading an object which
+/// does not exist fails with [`NotFoundError::ResourceNotFound`].
+pub trait ObjectStore: Debug + Send + Sync {
+    /// Resolves successfully only if the object exists.
+    fn exists<'a>(&'a self, object: &'a str) -> BoxFuture<'a, Result<(), AppError>>;
+
+    /// Returns the length of the object in bytes without downloading it.
+    fn size<'a>(&'a self, object: &'a str) -> BoxFuture<'a, Result<u64, AppError>>;
+
+    fn get<'a>(&'a self, object: &'a str) -> BoxFuture<'a, Result<Bytes, AppError>>;
+
+    /// Downloads at most the given number of leading bytes of the object.
+    fn get_range<'a>(
+        &'a self,
+        object: &'a str,
+        length: u64,
+    ) -> BoxFuture<'a, Result<Bytes, AppError>>;
+
+    fn put<'a>(
+        &'a self,
+        object: &'a str,
+        content_type: &'a str,
+        buffer: Bytes,
+    ) -> BoxFuture<'a, Result<(), AppError>>;
+
+    /// Creates a URL which lets anyone holding it download the object until it expires.
+    fn presign_get(&self, object: &str, response: &PresignedGet) -> Result<String, AppError>;
+
+    /// Creates a URL which lets anyone holding it upload an object of exactly the given content
+    /// type and length until it expires.
+    fn presign_put(
+        &self,
+        object: &str,
+        content_type: &str,
+        length: u64,
+        expires_in: StdDuration,
+    ) -> Result<String, AppError>;
+
+    /// Deletes every given object, ignoring those which do not exist.
+    fn delete<'a>(&'a self, objects: &'a [String]) -> BoxFuture<'a, Result<(), AppError>>;
+
+    /// Lists every object in the store along with when it was last modified.
+    fn list(&self) -> BoxFuture<'_, Result<Vec<StoredObject>, AppError>>;
+
+    /// Starts uploading an object in parts, returning the ID of the upload.
+    fn create_multipart_upload<'a>(
+        &'a self,
+        object: &'a str,
+        content_type: &'a str,
+    ) -> BoxFuture<'a, Result<String, AppError>>;
+
+    /// Creates a URL which lets anyone holding it upload a single part of a multipart upload
+    /// until it expires. Uploading the same part again replaces it.
+    fn presign_upload_part(
+        &self,
+        object: &str,
+        upload_id: &str,
+        part_number: u16,
+        expires_in: StdDuration,
+    ) -> Result<String, AppError>;
+
+    /// Lists the parts of a multipart upload which have been uploaded so far, ordered by their
+    /// part numbers.
+    fn list_parts<'a>(
+        &'a self,
+        object: &'a str,
+        upload_id: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<UploadedPart>, AppError>>;
+
+    /// Assembles the given parts into the object, ending the multipart upload.
+    fn complete_multipart_upload<'a>(
+        &'a self,
+        object: &'a str,
+        upload_id: &'a str,
+        parts: &'a [UploadedPart],
+    ) -> BoxFuture<'a, Result<(), AppError>>;
+
+    /// Discards a multipart upload along with every part uploaded to it.
+    fn abort_multipart_upload<'a>(
+        &'a self,
+        object: &'a str,
+        upload_id: &'a str,
+    ) -> BoxFuture<'a, Result<(), AppError>>;
+
+    /// Returns the store as a [`LocalStore`], whose presigned URLs are served by the server itself.
+    fn as_local(&self) -> Option<&LocalStore> {
+        None
+    }
+}
+
+#[derive(Debug)]
+pub struct UploadedPart {
+    pub part_number: u16,
+    pub etag: String,
+    pub size: u64,
+}
+
+#[derive(Debug)]
+pub struct StoredObject {
+    pub name: String,
+    pub last_modified: DateTime<Utc>,
+}
+
+/// The response headers which a presigned download URL is served with.
+#[derive(Debug)]
+pub struct PresignedGet {
+    pub cache_control: &'static str,
+    pub content_disposition: String,
+    pub content_type: &'static str,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl PresignedGet {
+    #[must_use]
+    pub fn expires_in(&self) -> StdDuration {
+        (self.expires_at - Utc::now())
+            .to_std()
+            .unwrap_or(StdDuration::ZERO)
+    }
+
+    #[must_use]
+    pub fn http_date(&self) -> String {
+        self.expires_at
+            .format("%a, %d %b %Y %H:%M:%S GMT")
+            .to_string()
+    }
+}
 
 #[derive(Clone, Debug)]
-pub struct R2Bucket {
-    http: ReqwestClient,
-    inner: Arc<Bucket>,
-    creds: Arc<Credentials>,
-    presign_cache: Arc<HashIndex<String, (Arc<str>, DateTime<Utc>)>>,
+pub struct Bucket {
+    store: Arc<dyn ObjectStore>,
+    presign_cache: Arc<PresignCache>,
+    preview_page_limit: u32,
 }
 
-impl R2Bucket {
-    pub fn new(
-        http: ReqwestClient,
-        account_id: &str,
-        bucket_name: String,
-        access_key_id: &str,
-        secret_access_key: &str,
-    ) -> AnyhowResult<Self> {
-        Ok(Self {
-            http,
-            inner: Arc::new(Bucket::new(
-                format!("https://{account_id}.r2.cloudflarestorage.com").parse()?,
-                UrlStyle::VirtualHost,
-                bucket_name,
-                "auto",
-            )?),
-            creds: Arc::new(Credentials::new(access_key_id, secret_access_key)),
+impl Bucket {
+    pub fn new(store: impl ObjectStore + 'static, preview_page_limit: u32) -> Self {
+        Self {
+            store: Arc::new(store),
             presign_cache: Arc::new(HashIndex::new()),
-        })
+            preview_page_limit,
+        }
+    }
+
+    #[must_use]
+    pub fn local_store(&self) -> Option<&LocalStore> {
+        self.store.as_local()
     }
 
     #[tracing::instrument(skip_all, err)]
     pub async fn exists(&self, object_key: &str, filetype: FileType) -> Result<(), AppError> {
-        let url = self
-            .inner
-            .head_object(Some(&self.creds), &format!("/{object_key}.{filetype}"))
-            .sign(DEFAULT_SIGN_DURATION);
-        self.http.head(url).send().await?.error_for_status()?;
-
-        Ok(())
+        self.store.exists(&format!("{object_key}.{filetype}")).await
     }
 
+    /// Downloads the printable version of a file, which falls back to the original file if it
+    /// has not been converted yet and thumbnails can be rendered from it, see
+    /// [`FileType::is_thumbnailable`].
     #[tracing::instrument(skip_all, err)]
     pub async fn get_file_for_thumbnail_processing(
         &self,
         object_key: &str,
         filetype: FileType,
     ) -> Result<Bytes, AppError> {
-        let url = self
-            .inner
-            .get_object(Some(&self.creds), &format!("/{object_key}.{filetype}"))
-            .sign(DEFAULT_SIGN_DURATION);
+        let printable = filetype.printable();
+        if printable != filetype {
+            match self.store.get(&format!("{object_key}.{printable}")).await {
+                Ok(buffer) => return Ok(buffer),
+                Err(AppError::NotFound(_)) if filetype.is_thumbnailable() => {}
+                Err(err) => return Err(err),
+            }
+        }
 
-        Ok(self
-            .http
-            .get(url)
-            .send()
-            .await?
-            .error_for_status()?
-            .bytes()
-            .await?)
+        self.get_file(object_key, filetype).await
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    pub async fn get_file(&self, object_key: &str, filetype: FileType) -> Result<Bytes, AppError> {
+        self.store.get(&format!("{object_key}.{filetype}")).await
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    pub async fn get_file_size(
+        &self,
+        object_key: &str,
+        filetype: FileType,
+    ) -> Result<u64, AppError> {
+        self.store.size(&format!("{object_key}.{filetype}")).await
+    }
+
+    /// Downloads only as many leading bytes of a file as [`FileType::sniff`] needs.
+    #[tracing::instrument(skip_all, err)]
+    pub async fn get_file_header(
+        &self,
+        object_key: &str,
+        filetype: FileType,
+    ) -> Result<Bytes, AppError> {
+        self.store
+            .get_range(&format!("{object_key}.{filetype}"), FileType::SNIFF_LENGTH)
+            .await
+    }
+
+    /// Stores the printable version of a file, see [`FileType::printable`].
+    #[tracing::instrument(skip_all, err)]
+    pub async fn put_converted_file(
+        &self,
+        buffer: Bytes,
+        object_key: &str,
+        filetype: FileType,
+    ) -> Result<(), AppError> {
+        let printable = filetype.printable();
+        self.store
+            .put(
+                &format!("{object_key}.{printable}"),
+                printable.to_mime(),
+                buffer,
+            )
+            .await
     }
 
     #[tracing::instrument(skip_all, err)]
@@ -88,34 +257,22 @@ impl R2Bucket {
         filename: &str,
         filetype: FileType,
     ) -> Result<Arc<str>, AppError> {
-        let object = format!("/{object_key}.{filetype}");
-        if let Some(Some(presigned)) = self.presign_cache.peek_with(&object, |k, presigned| {
-            if presigned.1 < Utc::now() {
-                self.presign_cache.remove(k);
-                None
-            } else {
-                Some(Arc::clone(&presigned.0))
-            }
-        }) {
+        // Files are always downloaded in the format which they are printed in
+        let filetype = filetype.printable();
+        let object = format!("{object_key}.{filetype}");
+        if let Some(presigned) = self.peek_presign_cache(&object) {
             return Ok(presigned);
         }
 
-        let expiry = Utc::now() + TimeDelta::hours(1);
-        let mut get_object = self.inner.get_object(Some(&self.creds), &object);
-        let query_params = get_object.query_mut();
-        query_params.insert("response-cache-control", "must-revalidate, private");
-        query_params.insert(
-            "response-content-disposition",
-            format!("attachment; filename*=UTF-8''{filename}.{filetype}"),
-        );
-        query_params.insert("response-content-type", filetype.to_mime());
-        query_params.insert(
-            "response-expires",
-            expiry.format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
-        );
-        let presigned = Arc::from(get_object.sign(StdDuration::from_secs(3600)).as_ref());
+        let response = PresignedGet {
+            cache_control: "must-revalidate, private",
+            content_disposition: format!("attachment; filename*=UTF-8''{filename}.{filetype}"),
+            content_type: filetype.to_mime(),
+            expires_at: Utc::now() + TimeDelta::hours(1),
+        };
+        let presigned = Arc::from(self.store.presign_get(&object, &response)?);
         self.presign_cache
-            .insert(object, (Arc::clone(&presigned), expiry))
+            .insert(object, (Arc::clone(&presigned), response.expires_at))
             .ok();
 
         Ok(presigned)
@@ -127,44 +284,63 @@ impl R2Bucket {
         object_key: &str,
         filetype: FileType,
     ) -> Result<Option<Arc<str>>, AppError> {
-        let thumbnail_object = format!("/{object_key}.t.webp");
-        if let Some(Some(presigned)) =
-            self.presign_cache
-                .peek_with(&thumbnail_object, |k, presigned| {
-                    if presigned.1 < Utc::now() {
-                        self.presign_cache.remove(k);
-                        None
-                    } else {
-                        Some(Arc::clone(&presigned.0))
-                    }
-                })
-        {
+        let thumbnail_object = thumbnail_object(object_key, None);
+        if let Some(presigned) = self.peek_presign_cache(&thumbnail_object) {
             return Ok(Some(presigned));
         }
 
         if self.exists(object_key, filetype).await.is_err() {
             return Err(AppError::NotFound(NotFoundError::ResourceNotFound));
         }
-        if self.exists(object_key, FileType::Webp).await.is_err() {
+
+        self.presign_get_thumbnail(thumbnail_object).await
+    }
+
+    /// Presigns the previews of the first pages of a file concurrently, in the order of their
+    /// pages. Pages whose previews have not been rendered yet are missing.
+    #[tracing::instrument(skip_all, err)]
+    pub async fn presign_get_file_pages(
+        &self,
+        object_key: &str,
+        filetype: FileType,
+        pages: u32,
+    ) -> Result<Vec<Option<Arc<str>>>, AppError> {
+        if self.exists(object_key, filetype).await.is_err() {
+            return Err(AppError::NotFound(NotFoundError::ResourceNotFound));
+        }
+
+        future::try_join_all((1..=pages).map(|page| {
+            let thumbnail_object = thumbnail_object(object_key, Some(page));
+            async move {
+                match self.peek_presign_cache(&thumbnail_object) {
+                    Some(presigned) => Ok(Some(presigned)),
+                    None => self.presign_get_thumbnail(thumbnail_object).await,
+                }
+            }
+        }))
+        .await
+    }
+
+    async fn presign_get_thumbnail(
+        &self,
+        thumbnail_object: String,
+    ) -> Result<Option<Arc<str>>, AppError> {
+        if self.store.exists(&thumbnail_object).await.is_err() {
             return Ok(None);
         }
 
-        let expiry = Utc::now() + TimeDelta::hours(1);
-        let mut get_object = self.inner.get_object(Some(&self.creds), &thumbnail_object);
-        let query_params = get_object.query_mut();
-        query_params.insert(
-            "response-cache-control",
-            "max-age=3600, must-revalidate, private",
-        );
-        query_params.insert("response-content-disposition", "inline");
-        query_params.insert("response-content-type", FileType::Webp.to_mime());
-        query_params.insert(
-            "response-expires",
-            expiry.format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
-        );
-        let presigned = Arc::from(get_object.sign(StdDuration::from_secs(3600)).as_ref());
+        let response = PresignedGet {
+            cache_control: "max-age=3600, must-revalidate, private",
+            content_disposition: String::from("inline"),
+            content_type: FileType::Thumbnail.to_mime(),
+            expires_at: Utc::now() + TimeDelta::hours(1),
+        };
+        let presigned = Arc::from(self.store.presign_get(&thumbnail_object, &response)?);
         self.presign_cache
-            .insert_async(thumbnail_object.clone(), (Arc::clone(&presigned), expiry))
+            .insert_async(
+                thumbnail_object,
+                (Arc::clone(&presigned), response.expires_at),
+            )
             .await
             .ok();
 
@@ -172,70 +348,135 @@ impl R2Bucket {
     }
 
     #[tracing::instrument(skip_all, err)]
-    pub async fn put_thumbnail(&self, buffer: Bytes, object_key: &str) -> Result<(), AppError> {
-        let url = self
-            .inner
-            .put_object(Some(&self.creds), &format!("/{object_key}.t.webp"))
-            .sign(DEFAULT_SIGN_DURATION);
-        self.http
-            .put(url)
-            .header(CONTENT_TYPE, FileType::Webp.to_mime())
-            .body(buffer)
-            .send()
-            .await?
-            .error_for_status()?;
-
-        Ok(())
+    pub async fn put_thumbnail(
+        &self,
+        buffer: Bytes,
+        object_key: &str,
+        page: Option<u32>,
+    ) -> Result<(), AppError> {
+        self.store
+            .put(
+                &thumbnail_object(object_key, page),
+                FileType::Thumbnail.to_mime(),
+                buffer,
+            )
+            .await
     }
 
     #[tracing::instrument(skip_all, err)]
     pub fn presign_put(
         &self,
-        created_at: &DateTime<Utc>,
         filetype: FileType,
         length: u64,
         object_key: &str,
     ) -> Result<String, AppError> {
-        // 50 MB
-        if length > 50 * 1_000_000 {
+        check_file_size(length)?;
+
+        self.store.presign_put(
+            &format!("{object_key}.{filetype}"),
+            filetype.to_mime(),
+            length,
+            UPLOAD_EXPIRY,
+        )
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    pub async fn create_multipart_upload(
+        &self,
+        object_key: &str,
+        filetype: FileType,
+        length: u64,
+    ) -> Result<String, AppError> {
+        check_file_size(length)?;
+
+        self.store
+            .create_multipart_upload(&format!("{object_key}.{filetype}"), filetype.to_mime())
+            .await
+    }
+
+    /// Presigns the parts of a multipart upload which have not been uploaded yet. Every part is
+    /// [`MULTIPART_PART_SIZE`] long, except for the last part.
+    #[tracing::instrument(skip_all, err)]
+    pub async fn presign_multipart_upload(
+        &self,
+        object_key: &str,
+        filetype: FileType,
+        length: u64,
+        upload_id: &str,
+    ) -> Result<MultipartUpload, AppError> {
+        let object = format!("{object_key}.{filetype}");
+        let uploaded_parts = self
+            .store
+            .list_parts(&object, upload_id)
+            .await?
+            .into_iter()
+            .map(|part| part.part_number)
+            .collect::<Vec<_>>();
+        let parts = (1..=multipart_parts_len(length))
+            .filter(|part_number| !uploaded_parts.contains(part_number))
+            .map(|part_number| {
+                Ok(MultipartUploadPart {
+                    part_number,
+                    url: self.store.presign_upload_part(
+                        &object,
+                        upload_id,
+                        part_number,
+                        UPLOAD_EXPIRY,
+                    )?,
+                })
+            })
+            .collect::<Result<Vec<_>, AppError>>()?;
+
+        Ok(MultipartUpload {
+            part_size: MULTIPART_PART_SIZE,
+            uploaded_parts,
+            parts,
+        })
+    }
+
+    /// Completes a multipart upload once every part of the file has been uploaded.
+    #[tracing::instrument(skip_all, err)]
+    pub async fn complete_multipart_upload(
+        &self,
+        object_key: &str,
+        filetype: FileType,
+        length: u64,
+        upload_id: &str,
+    ) -> Result<(), AppError> {
+        let object = format!("{object_key}.{filetype}");
+        let parts = self.store.list_parts(&object, upload_id).await?;
+        if !parts
+            .iter()
+            .zip(1..)
+            .all(|(part, part_number)| part.part_number == part_number)
+            || parts.len() != usize::from(multipart_parts_len(length))
+            || parts.iter().map(|part| part.size).sum::<u64>() != length
+        {
             return Err(AppError::BadRequest(BadRequestError::MalformedFiles(
-                "File size exceeded limit.",
+                "File has not been fully uploaded.".into(),
             )));
         }
 
-        let object = format!("/{object_key}.{filetype}");
-        let expiry = (TimeDelta::minutes(15) - (Utc::now() - *created_at))
-            .to_std()
-            .unwrap();
-        let mut put_object = self.inner.put_object(Some(&self.creds), &object);
-        let headers = put_object.headers_mut();
-        headers.insert("content-type", filetype.to_mime());
-        headers.insert("content-length", length.to_string());
+        self.store
+            .complete_multipart_upload(&object, upload_id, &parts)
+            .await
+    }
 
-        Ok(put_object.sign(expiry).into())
+    #[tracing::instrument(skip_all, err)]
+    pub async fn abort_multipart_upload(
+        &self,
+        object_key: &str,
+        filetype: FileType,
+        upload_id: &str,
+    ) -> Result<(), AppError> {
+        self.store
+            .abort_multipart_upload(&format!("{object_key}.{filetype}"), upload_id)
+            .await
     }
 
     #[tracing::instrument(skip_all, err)]
     pub async fn delete_file(&self, object_key: &str, filetype: FileType) -> Result<(), AppError> {
-        let objects = [
-            ObjectIdentifier::new(format!("/{object_key}.{filetype}")),
-            ObjectIdentifier::new(format!("/{object_key}.t.webp")),
-        ];
-
-        let mut delete_objects = self.inner.delete_objects(Some(&self.creds), objects.iter());
-        delete_objects.set_quiet(true);
-        let url = delete_objects.sign(DEFAULT_SIGN_DURATION);
-        let (body, content_md5) = delete_objects.body_with_md5();
-        self.http
-            .post(url)
-            .header(CONTENT_TYPE, "application/xml")
-            .header("content-md5", content_md5)
-            .body(body)
-            .send()
-            .await?
-            .error_for_status()?;
-
-        Ok(())
+        self.delete_files(&[(object_key, filetype)]).await
     }
 
     #[tracing::instrument(skip_all, err)]
@@ -247,25 +488,90 @@ impl R2Bucket {
             .iter()
             .flat_map(|(object_key, filetype)| {
                 [
-                    ObjectIdentifier::new(format!("/{object_key}.{filetype}")),
-                    ObjectIdentifier::new(format!("/{object_key}.t.webp")),
+                    format!("{object_key}.{filetype}"),
+                    thumbnail_object(object_key.as_ref(), None),
                 ]
+                .into_iter()
+                .chain(
+                    (filetype.printable() != *filetype)
+                        .then(|| format!("{object_key}.{}", filetype.printable())),
+                )
+                .chain(
+                    (1..=self.preview_page_limit)
+                        .map(|page| thumbnail_object(object_key.as_ref(), Some(page))),
+                )
             })
-            .collect::<Vec<ObjectIdentifier>>();
-
-        let mut delete_objects = self.inner.delete_objects(Some(&self.creds), objects.iter());
-        delete_objects.set_quiet(true);
-        let url = delete_objects.sign(DEFAULT_SIGN_DURATION);
-        let (body, content_md5) = delete_objects.body_with_md5();
-        self.http
-            .post(url)
-            .header(CONTENT_TYPE, "application/xml")
-            .header("content-md5", content_md5)
-            .body(body)
-            .send()
+            .collect::<Vec<_>>();
+
+        self.store.delete(&objects).await
+    }
+
+    /// Finds the objects last modified before the grace period which do not belong to any of the
+    /// given object keys, such as files whose order was never built or thumbnails left behind by
+    /// deleted files.
+    #[tracing::instrument(skip_all, err)]
+    pub async fn find_orphans(
+        &self,
+        object_keys: &HashSet<String>,
+        grace_period: TimeDelta,
+    ) -> Result<Vec<String>, AppError> {
+        let cutoff = Utc::now() - grace_period;
+
+        Ok(self
+            .store
+            .list()
             .await?
-            .error_for_status()?;
+            .into_iter()
+            .filter(|object| {
+                let object_key = object
+                    .name
+                    .split_once('.')
+                    .map_or(&*object.name, |(key, _)| key);
+                object.last_modified < cutoff && !object_keys.contains(object_key)
+            })
+            .map(|object| object.name)
+            .collect())
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    pub async fn delete_objects(&self, objects: &[String]) -> Result<(), AppError> {
+        self.store.delete(objects).await
+    }
+
+    fn peek_presign_cache(&self, object: &str) -> Option<Arc<str>> {
+        self.presign_cache
+            .peek_with(object, |k, presigned| {
+                if presigned.1 < Utc::now() {
+                    self.presign_cache.remove(k);
+                    None
+                } else {
+                    Some(Arc::clone(&presigned.0))
+                }
+            })
+            .flatten()
+    }
+}
+
+fn check_file_size(length: u64) -> Result<(), AppError> {
+    if length > MAX_FILE_SIZE {
+        return Err(AppError::BadRequest(BadRequestError::MalformedFiles(
+            "File size exceeded limit.".into(),
+        )));
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn multipart_parts_len(length: u64) -> u16 {
+    // Bounded by `MAX_FILE_SIZE`
+    length.div_ceil(MULTIPART_PART_SIZE).max(1) as u16
+}
 
-        Ok(())
+/// Builds the object name of the thumbnail of a file, or of the preview of one of its pages.
+fn thumbnail_object(object_key: &str, page: Option<u32>) -> String {
+    match page {
+        Some(page) => format!("{object_key}.p{page}.t.webp"),
+        None => format!("{object_key}.t.webp"),
     }
 }
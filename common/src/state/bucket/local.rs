@@ -0,0 +1,496 @@
+use std::{
+    fs::File,
+    io::{ErrorKind, Read as _},
+    path::{Component, Path, PathBuf},
+    time::Duration as StdDuration,
+};
+
+use anyhow::{Context as _, Result as AnyhowResult};
+use bytes::Bytes;
+use chrono::{TimeDelta, Utc};
+use futures::{FutureExt as _, future::BoxFuture};
+use hmac::{Hmac, Mac as _};
+use reqwest::Url;
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::{
+    AppError,
+    error::{ForbiddenError, NotFoundError},
+};
+
+use super::{ObjectStore, PresignedGet, StoredObject, UploadedPart};
+
+/// An [`ObjectStore`] which keeps every object as a file under a directory on the local
+/// filesystem. Presigned URLs point back to the server itself and are signed with HMAC.
+#[derive(Debug)]
+pub struct LocalStore {
+    directory: PathBuf,
+    base_url: Url,
+    hmac_instance: Hmac<Sha256>,
+}
+
+impl LocalStore {
+    pub fn new(directory: PathBuf, root_uri: &str, secret: &[u8]) -> AnyhowResult<Self> {
+        std::fs::create_dir_all(&directory).with_context(|| {
+            format!(
+                "Unable to create storage directory `{}`",
+                directory.display()
+            )
+        })?;
+
+        Ok(Self {
+            directory,
+            base_url: format!("{}/storage/", root_uri.trim_end_matches('/')).parse()?,
+            hmac_instance: Hmac::new_from_slice(secret).unwrap(), // Infallible
+        })
+    }
+
+    /// Resolves the path of an object, refusing any name which could escape the storage
+    /// directory.
+    fn path(&self, object: &str) -> Result<PathBuf, AppError> {
+        let mut components = Path::new(object).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(name)), None) => Ok(self.directory.join(name)),
+            _ => Err(AppError::NotFound(NotFoundError::ResourceNotFound)),
+        }
+    }
+
+    /// Verifies the signature of a URL created by [`LocalStore::presign_get`] or
+    /// [`LocalStore::presign_put`], given its raw query string and the `expires` parameter parsed
+    /// from it.
+    #[tracing::instrument(skip_all, err)]
+    pub fn verify(
+        &self,
+        method: &str,
+        object: &str,
+        query: &str,
+        expires: i64,
+    ) -> Result<(), AppError> {
+        let is_valid = query
+            .rsplit_once("&signature=")
+            .and_then(|(query, signature)| Some((query, hex::decode(signature).ok()?)))
+            .is_some_and(|(query, signature)| {
+                self.hmac_instance
+                    .clone()
+                    .chain_update(format!("{method}\n{object}\n{query}"))
+                    .verify_slice(&signature)
+                    .is_ok()
+            });
+        if !is_valid || expires < Utc::now().timestamp() {
+            return Err(AppError::Forbidden(ForbiddenError::Inaccessible));
+        }
+
+        Ok(())
+    }
+
+    /// Stores a part of a multipart upload created by [`LocalStore::presign_upload_part`].
+    #[tracing::instrument(skip_all, err)]
+    pub async fn put_part(
+        &self,
+        object: &str,
+        upload_id: &str,
+        part_number: u16,
+        buffer: Bytes,
+    ) -> Result<(), AppError> {
+        self.exists(&upload_marker(object, upload_id)).await?;
+
+        write_atomically(
+            self.path(&format!("{object}.{upload_id}.{part_number}.part"))?,
+            buffer,
+        )
+        .await
+    }
+
+    async fn remove_multipart_upload(
+        &self,
+        object: &str,
+        upload_id: &str,
+        parts: &[UploadedPart],
+    ) -> Result<(), AppError> {
+        let objects = parts
+            .iter()
+            .map(|part| format!("{object}.{upload_id}.{}.part", part.part_number))
+            .chain([upload_marker(object, upload_id)])
+            .collect::<Vec<_>>();
+
+        self.delete(&objects).await
+    }
+
+    fn sign(&self, method: &str, object: &str, params: &[(&str, &str)]) -> String {
+        let mut url = self.base_url.join(object).unwrap(); // Object names are plain file names
+        url.query_pairs_mut().extend_pairs(params);
+        let signature = self
+            .hmac_instance
+            .clone()
+            .chain_update(format!("{method}\n{object}\n{}", url.query().unwrap_or("")))
+            .finalize()
+            .into_bytes();
+        url.query_pairs_mut()
+            .append_pair("signature", &hex::encode(signature));
+
+        url.into()
+    }
+}
+
+impl ObjectStore for LocalStore {
+    fn exists<'a>(&'a self, object: &'a str) -> BoxFuture<'a, Result<(), AppError>> {
+        async move {
+            if tokio::fs::try_exists(self.path(object)?)
+                .await
+                .context("Unable to access storage directory")?
+            {
+                Ok(())
+            } else {
+                Err(AppError::NotFound(NotFoundError::ResourceNotFound))
+            }
+        }
+        .boxed()
+    }
+
+    fn size<'a>(&'a self, object: &'a str) -> BoxFuture<'a, Result<u64, AppError>> {
+        async move { Ok(tokio::fs::metadata(self.path(object)?).await?.len()) }.boxed()
+    }
+
+    fn get<'a>(&'a self, object: &'a str) -> BoxFuture<'a, Result<Bytes, AppError>> {
+        async move { Ok(tokio::fs::read(self.path(object)?).await?.into()) }.boxed()
+    }
+
+    fn get_range<'a>(
+        &'a self,
+        object: &'a str,
+        length: u64,
+    ) -> BoxFuture<'a, Result<Bytes, AppError>> {
+        async move {
+            let path = self.path(object)?;
+            let buffer = tokio::task::spawn_blocking(move || {
+                let mut buffer = Vec::new();
+                File::open(path)?.take(length).read_to_end(&mut buffer)?;
+
+                Ok::<_, std::io::Error>(buffer)
+            })
+            .await??;
+
+            Ok(buffer.into())
+        }
+        .boxed()
+    }
+
+    fn put<'a>(
+        &'a self,
+        object: &'a str,
+        _content_type: &'a str,
+        buffer: Bytes,
+    ) -> BoxFuture<'a, Result<(), AppError>> {
+        async move { write_atomically(self.path(object)?, buffer).await }.boxed()
+    }
+
+    fn presign_get(&self, object: &str, response: &PresignedGet) -> Result<String, AppError> {
+        Ok(self.sign(
+            "GET",
+            object,
+            &[
+                ("expires", &response.expires_at.timestamp().to_string()),
+                ("cache-control", response.cache_control),
+                ("content-disposition", &response.content_disposition),
+                ("content-type", response.content_type),
+            ],
+        ))
+    }
+
+    fn presign_put(
+        &self,
+        object: &str,
+        content_type: &str,
+        length: u64,
+        expires_in: StdDuration,
+    ) -> Result<String, AppError> {
+        let expires_at = Utc::now() + TimeDelta::from_std(expires_in).unwrap_or(TimeDelta::zero());
+
+        Ok(self.sign(
+            "PUT",
+            object,
+            &[
+                ("expires", &expires_at.timestamp().to_string()),
+                ("content-type", content_type),
+                ("content-length", &length.to_string()),
+            ],
+        ))
+    }
+
+    fn delete<'a>(&'a self, objects: &'a [String]) -> BoxFuture<'a, Result<(), AppError>> {
+        async move {
+            for object in objects {
+                match tokio::fs::remove_file(self.path(object)?).await {
+                    Err(err) if err.kind() != ErrorKind::NotFound => return Err(err.into()),
+                    _ => {}
+                }
+            }
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn list(&self) -> BoxFuture<'_, Result<Vec<StoredObject>, AppError>> {
+        async move {
+            let mut objects = Vec::new();
+            let mut entries = tokio::fs::read_dir(&self.directory).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let Ok(name) = entry.file_name().into_string() else {
+                    continue;
+                };
+
+                objects.push(StoredObject {
+                    name,
+                    last_modified: entry.metadata().await?.modified()?.into(),
+                });
+            }
+
+            Ok(objects)
+        }
+        .boxed()
+    }
+
+    fn as_local(&self) -> Option<&LocalStore> {
+        Some(self)
+    }
+
+    fn create_multipart_upload<'a>(
+        &'a self,
+        object: &'a str,
+        _content_type: &'a str,
+    ) -> BoxFuture<'a, Result<String, AppError>> {
+        async move {
+            let upload_id = Uuid::new_v4().simple().to_string();
+            tokio::fs::write(self.path(&upload_marker(object, &upload_id))?, b"").await?;
+
+            Ok(upload_id)
+        }
+        .boxed()
+    }
+
+    fn presign_upload_part(
+        &self,
+        object: &str,
+        upload_id: &str,
+        part_number: u16,
+        expires_in: StdDuration,
+    ) -> Result<String, AppError> {
+        let expires_at = Utc::now() + TimeDelta::from_std(expires_in).unwrap_or(TimeDelta::zero());
+
+        Ok(self.sign(
+            "PUT",
+            object,
+            &[
+                ("expires", &expires_at.timestamp().to_string()),
+                ("upload-id", upload_id),
+                ("part-number", &part_number.to_string()),
+            ],
+        ))
+    }
+
+    fn list_parts<'a>(
+        &'a self,
+        object: &'a str,
+        upload_id: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<UploadedPart>, AppError>> {
+        async move {
+            self.exists(&upload_marker(object, upload_id)).await?;
+
+            let prefix = format!("{object}.{upload_id}.");
+            let mut parts = Vec::new();
+            let mut entries = tokio::fs::read_dir(&self.directory).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let Some(part_number) = entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|name| name.strip_prefix(&prefix)?.strip_suffix(".part"))
+                    .and_then(|part_number| part_number.parse().ok())
+                else {
+                    continue;
+                };
+
+                parts.push(UploadedPart {
+                    part_number,
+                    etag: String::new(),
+                    size: entry.metadata().await?.len(),
+                });
+            }
+            parts.sort_unstable_by_key(|part| part.part_number);
+
+            Ok(parts)
+        }
+        .boxed()
+    }
+
+    fn complete_multipart_upload<'a>(
+        &'a self,
+        object: &'a str,
+        upload_id: &'a str,
+        parts: &'a [UploadedPart],
+    ) -> BoxFuture<'a, Result<(), AppError>> {
+        async move {
+            let length = parts.iter().map(|part| part.size).sum::<u64>();
+            let mut buffer = Vec::with_capacity(usize::try_from(length).unwrap_or_default());
+            for part in parts {
+                buffer.extend(
+                    tokio::fs::read(
+                        self.path(&format!("{object}.{upload_id}.{}.part", part.part_number))?,
+                    )
+                    .await?,
+                );
+            }
+            write_atomically(self.path(object)?, buffer.into()).await?;
+
+            self.remove_multipart_upload(object, upload_id, parts).await
+        }
+        .boxed()
+    }
+
+    fn abort_multipart_upload<'a>(
+        &'a self,
+        object: &'a str,
+        upload_id: &'a str,
+    ) -> BoxFuture<'a, Result<(), AppError>> {
+        async move {
+            let parts = self.list_parts(object, upload_id).await?;
+
+            self.remove_multipart_upload(object, upload_id, &parts)
+                .await
+        }
+        .boxed()
+    }
+}
+
+/// The name of the empty object which marks a multipart upload as in progress.
+fn upload_marker(object: &str, upload_id: &str) -> String {
+    format!("{object}.{upload_id}.upload")
+}
+
+/// Writes to a temporary file first so that readers never see a partially written object.
+async fn write_atomically(path: PathBuf, buffer: Bytes) -> Result<(), AppError> {
+    let mut partial_path = path.clone().into_os_string();
+    partial_path.push(".partial");
+    tokio::fs::write(&partial_path, buffer).await?;
+    tokio::fs::rename(partial_path, path).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+    use reqwest::Url;
+    use uuid::Uuid;
+
+    use super::{LocalStore, ObjectStore as _, PresignedGet};
+
+    fn local_store() -> LocalStore {
+        LocalStore::new(
+            std::env::temp_dir().join(format!("graphein-test-{}", Uuid::new_v4())),
+            "http://localhost:8000/",
+            b"secret",
+        )
+        .unwrap()
+    }
+
+    /// Splits a presigned URL into its object, raw query string and `expires` parameter, the way
+    /// the storage routes receive them.
+    fn split(url: &str) -> (String, String, i64) {
+        let url = Url::parse(url).unwrap();
+        let object = url.path().strip_prefix("/storage/").unwrap().to_string();
+        let expires = url
+            .query_pairs()
+            .find(|(key, _)| key == "expires")
+            .unwrap()
+            .1
+            .parse()
+            .unwrap();
+
+        (object, url.query().unwrap().to_string(), expires)
+    }
+
+    #[test]
+    fn verifies_its_own_presigned_urls() {
+        let local_store = local_store();
+        let presigned_get = local_store
+            .presign_get(
+                "0123abcd.pdf",
+                &PresignedGet {
+                    cache_control: "private",
+                    content_disposition: String::from("attachment; filename*=UTF-8''a b.pdf"),
+                    content_type: "application/pdf",
+                    expires_at: Utc::now() + TimeDelta::hours(1),
+                },
+            )
+            .unwrap();
+        let (object, query, expires) = split(&presigned_get);
+        assert_eq!(object, "0123abcd.pdf");
+        assert!(local_store.verify("GET", &object, &query, expires).is_ok());
+        assert!(local_store.verify("PUT", &object, &query, expires).is_err());
+        assert!(
+            local_store
+                .verify("GET", "4567cdef.pdf", &query, expires)
+                .is_err()
+        );
+
+        let presigned_put = local_store
+            .presign_put(
+                "0123abcd.pdf",
+                "application/pdf",
+                1024,
+                std::time::Duration::from_secs(60),
+            )
+            .unwrap();
+        let (object, query, expires) = split(&presigned_put);
+        assert!(local_store.verify("PUT", &object, &query, expires).is_ok());
+    }
+
+    #[test]
+    fn rejects_tampered_and_expired_urls() {
+        let local_store = local_store();
+        let presigned_put = local_store
+            .presign_put(
+                "0123abcd.pdf",
+                "application/pdf",
+                1024,
+                std::time::Duration::from_secs(60),
+            )
+            .unwrap();
+        let (object, query, expires) = split(&presigned_put);
+        let tampered = query.replace("content-length=1024", "content-length=4096");
+        assert!(
+            local_store
+                .verify("PUT", &object, &tampered, expires)
+                .is_err()
+        );
+        assert!(local_store.verify("PUT", &object, "", expires).is_err());
+
+        let expired = (Utc::now() - TimeDelta::minutes(1)).timestamp();
+        let presigned_get =
+            local_store.sign("GET", "0123abcd.pdf", &[("expires", &expired.to_string())]);
+        let (object, query, expires) = split(&presigned_get);
+        assert!(local_store.verify("GET", &object, &query, expires).is_err());
+    }
+
+    #[test]
+    fn refuses_objects_outside_of_its_directory() {
+        let local_store = local_store();
+        assert!(local_store.path("0123abcd.pdf").is_ok());
+        for object in [
+            "",
+            ".",
+            "..",
+            "../secret",
+            "a/b.pdf",
+            "/etc/passwd",
+            "./a.pdf",
+        ] {
+            assert!(
+                local_store.path(object).is_err(),
+                "`{object}` should be refused"
+            );
+        }
+    }
+}
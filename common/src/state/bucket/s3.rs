@@ -0,0 +1,389 @@
+use std::time::Duration as StdDuration;
+
+use anyhow::{Result as AnyhowResult, anyhow};
+use bytes::Bytes;
+use chrono::DateTime;
+use futures::{FutureExt as _, future::BoxFuture};
+use http::{
+    StatusCode,
+    header::{CONTENT_LENGTH, CONTENT_TYPE, RANGE},
+};
+use reqwest::{Client as ReqwestClient, Response};
+use rusty_s3::{
+    Bucket, Credentials, S3Action as _, UrlStyle,
+    actions::{CreateMultipartUpload, ListObjectsV2, ListParts, ObjectIdentifier},
+};
+
+use crate::{AppError, error::NotFoundError};
+
+use super::{ObjectStore, PresignedGet, StoredObject, UploadedPart};
+
+const DEFAULT_SIGN_DURATION: StdDuration = StdDuration::from_secs(60);
+const MAX_DELETE_OBJECTS: usize = 1000;
+
+/// An [`ObjectStore`] backed by any S3-compatible service, such as Cloudflare R2 or `MinIO`.
+#[derive(Debug)]
+pub struct S3Store {
+    http: ReqwestClient,
+    inner: Bucket,
+    creds: Credentials,
+}
+
+impl S3Store {
+    pub fn new(
+        http: ReqwestClient,
+        endpoint: &str,
+        path_style: bool,
+        region: String,
+        bucket_name: String,
+        access_key_id: &str,
+        secret_access_key: &str,
+    ) -> AnyhowResult<Self> {
+        Ok(Self {
+            http,
+            inner: Bucket::new(
+                endpoint.parse()?,
+                if path_style {
+                    UrlStyle::Path
+                } else {
+                    UrlStyle::VirtualHost
+                },
+                bucket_name,
+                region,
+            )?,
+            creds: Credentials::new(access_key_id, secret_access_key),
+        })
+    }
+}
+
+impl ObjectStore for S3Store {
+    fn exists<'a>(&'a self, object: &'a str) -> BoxFuture<'a, Result<(), AppError>> {
+        async move {
+            let url = self
+                .inner
+                .head_object(Some(&self.creds), object)
+                .sign(DEFAULT_SIGN_DURATION);
+            check_object_status(self.http.head(url).send().await?)?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn size<'a>(&'a self, object: &'a str) -> BoxFuture<'a, Result<u64, AppError>> {
+        async move {
+            let url = self
+                .inner
+                .head_object(Some(&self.creds), object)
+                .sign(DEFAULT_SIGN_DURATION);
+            let response = check_object_status(self.http.head(url).send().await?)?;
+
+            response
+                .headers()
+                .get(CONTENT_LENGTH)
+                .and_then(|length| length.to_str().ok()?.parse().ok())
+                .ok_or_else(|| anyhow!("Object `{object}` has no content length").into())
+        }
+        .boxed()
+    }
+
+    fn get<'a>(&'a self, object: &'a str) -> BoxFuture<'a, Result<Bytes, AppError>> {
+        async move {
+            let url = self
+                .inner
+                .get_object(Some(&self.creds), object)
+                .sign(DEFAULT_SIGN_DURATION);
+
+            Ok(check_object_status(self.http.get(url).send().await?)?
+                .bytes()
+                .await?)
+        }
+        .boxed()
+    }
+
+    fn get_range<'a>(
+        &'a self,
+        object: &'a str,
+        length: u64,
+    ) -> BoxFuture<'a, Result<Bytes, AppError>> {
+        async move {
+            if length == 0 {
+                return Ok(Bytes::new());
+            }
+
+            let url = self
+                .inner
+                .get_object(Some(&self.creds), object)
+                .sign(DEFAULT_SIGN_DURATION);
+            let response = self
+                .http
+                .get(url)
+                .header(RANGE, format!("bytes=0-{}", length - 1))
+                .send()
+                .await?;
+            // Empty objects have no range to satisfy
+            if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+                return Ok(Bytes::new());
+            }
+
+            let mut buffer = check_object_status(response)?.bytes().await?;
+            buffer.truncate(usize::try_from(length).unwrap_or(usize::MAX));
+
+            Ok(buffer)
+        }
+        .boxed()
+    }
+
+    fn put<'a>(
+        &'a self,
+        object: &'a str,
+        content_type: &'a str,
+        buffer: Bytes,
+    ) -> BoxFuture<'a, Result<(), AppError>> {
+        async move {
+            let url = self
+                .inner
+                .put_object(Some(&self.creds), object)
+                .sign(DEFAULT_SIGN_DURATION);
+            self.http
+                .put(url)
+                .header(CONTENT_TYPE, content_type)
+                .body(buffer)
+                .send()
+                .await?
+                .error_for_status()?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn presign_get(&self, object: &str, response: &PresignedGet) -> Result<String, AppError> {
+        let mut get_object = self.inner.get_object(Some(&self.creds), object);
+        let query_params = get_object.query_mut();
+        query_params.insert("response-cache-control", response.cache_control);
+        query_params.insert(
+            "response-content-disposition",
+            response.content_disposition.as_str(),
+        );
+        query_params.insert("response-content-type", response.content_type);
+        query_params.insert("response-expires", response.http_date());
+
+        Ok(get_object.sign(response.expires_in()).into())
+    }
+
+    fn presign_put(
+        &self,
+        object: &str,
+        content_type: &str,
+        length: u64,
+        expires_in: StdDuration,
+    ) -> Result<String, AppError> {
+        let mut put_object = self.inner.put_object(Some(&self.creds), object);
+        let headers = put_object.headers_mut();
+        headers.insert("content-type", content_type);
+        headers.insert("content-length", length.to_string());
+
+        Ok(put_object.sign(expires_in).into())
+    }
+
+    fn delete<'a>(&'a self, objects: &'a [String]) -> BoxFuture<'a, Result<(), AppError>> {
+        async move {
+            for objects in objects.chunks(MAX_DELETE_OBJECTS) {
+                let objects = objects
+                    .iter()
+                    .map(|object| ObjectIdentifier::new(object.clone()))
+                    .collect::<Vec<_>>();
+                let mut delete_objects =
+                    self.inner.delete_objects(Some(&self.creds), objects.iter());
+                delete_objects.set_quiet(true);
+                let url = delete_objects.sign(DEFAULT_SIGN_DURATION);
+                let (body, content_md5) = delete_objects.body_with_md5();
+                self.http
+                    .post(url)
+                    .header(CONTENT_TYPE, "application/xml")
+                    .header("content-md5", content_md5)
+                    .body(body)
+                    .send()
+                    .await?
+                    .error_for_status()?;
+            }
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn list(&self) -> BoxFuture<'_, Result<Vec<StoredObject>, AppError>> {
+        async move {
+            let mut objects = Vec::new();
+            let mut continuation_token: Option<String> = None;
+            loop {
+                let mut list_objects = self.inner.list_objects_v2(Some(&self.creds));
+                if let Some(continuation_token) = &continuation_token {
+                    list_objects.with_continuation_token(continuation_token.as_str());
+                }
+                let url = list_objects.sign(DEFAULT_SIGN_DURATION);
+                let body = self
+                    .http
+                    .get(url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .bytes()
+                    .await?;
+
+                let response = ListObjectsV2::parse_response(&body).map_err(|err| anyhow!(err))?;
+                for object in response.contents {
+                    objects.push(StoredObject {
+                        last_modified: DateTime::parse_from_rfc3339(&object.last_modified)
+                            .map_err(|err| anyhow!(err))?
+                            .to_utc(),
+                        name: object.key,
+                    });
+                }
+
+                continuation_token = response.next_continuation_token;
+                if continuation_token.is_none() {
+                    break;
+                }
+            }
+
+            Ok(objects)
+        }
+        .boxed()
+    }
+
+    fn create_multipart_upload<'a>(
+        &'a self,
+        object: &'a str,
+        content_type: &'a str,
+    ) -> BoxFuture<'a, Result<String, AppError>> {
+        async move {
+            let url = self
+                .inner
+                .create_multipart_upload(Some(&self.creds), object)
+                .sign(DEFAULT_SIGN_DURATION);
+            let body = self
+                .http
+                .post(url)
+                .header(CONTENT_TYPE, content_type)
+                .send()
+                .await?
+                .error_for_status()?
+                .bytes()
+                .await?;
+
+            Ok(CreateMultipartUpload::parse_response(body)
+                .map_err(|err| anyhow!(err))?
+                .upload_id()
+                .to_owned())
+        }
+        .boxed()
+    }
+
+    fn presign_upload_part(
+        &self,
+        object: &str,
+        upload_id: &str,
+        part_number: u16,
+        expires_in: StdDuration,
+    ) -> Result<String, AppError> {
+        Ok(self
+            .inner
+            .upload_part(Some(&self.creds), object, part_number, upload_id)
+            .sign(expires_in)
+            .into())
+    }
+
+    fn list_parts<'a>(
+        &'a self,
+        object: &'a str,
+        upload_id: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<UploadedPart>, AppError>> {
+        async move {
+            // Files never have more parts than a single page of the listing holds
+            let url = self
+                .inner
+                .list_parts(Some(&self.creds), object, upload_id)
+                .sign(DEFAULT_SIGN_DURATION);
+            let body = self
+                .http
+                .get(url)
+                .send()
+                .await?
+                .error_for_status()?
+                .bytes()
+                .await?;
+
+            let mut parts = ListParts::parse_response(body)
+                .map_err(|err| anyhow!(err))?
+                .parts
+                .into_iter()
+                .map(|part| UploadedPart {
+                    part_number: part.number,
+                    etag: part.etag,
+                    size: part.size,
+                })
+                .collect::<Vec<_>>();
+            parts.sort_unstable_by_key(|part| part.part_number);
+
+            Ok(parts)
+        }
+        .boxed()
+    }
+
+    fn complete_multipart_upload<'a>(
+        &'a self,
+        object: &'a str,
+        upload_id: &'a str,
+        parts: &'a [UploadedPart],
+    ) -> BoxFuture<'a, Result<(), AppError>> {
+        async move {
+            let complete_multipart_upload = self.inner.complete_multipart_upload(
+                Some(&self.creds),
+                object,
+                upload_id,
+                parts.iter().map(|part| part.etag.as_str()),
+            );
+            let url = complete_multipart_upload.sign(DEFAULT_SIGN_DURATION);
+            self.http
+                .post(url)
+                .header(CONTENT_TYPE, "application/xml")
+                .body(complete_multipart_upload.body())
+                .send()
+                .await?
+                .error_for_status()?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn abort_multipart_upload<'a>(
+        &'a self,
+        object: &'a str,
+        upload_id: &'a str,
+    ) -> BoxFuture<'a, Result<(), AppError>> {
+        async move {
+            let url = self
+                .inner
+                .abort_multipart_upload(Some(&self.creds), object, upload_id)
+                .sign(DEFAULT_SIGN_DURATION);
+            self.http.delete(url).send().await?.error_for_status()?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// Fails with [`NotFoundError::ResourceNotFound`] if the requested object does not exist, or with
+/// an internal error for any other unsuccessful response.
+fn check_object_status(response: Response) -> Result<Response, AppError> {
+    if response.status() == StatusCode::NOT_FOUND {
+        return Err(AppError::NotFound(NotFoundError::ResourceNotFound));
+    }
+
+    Ok(response.error_for_status()?)
+}
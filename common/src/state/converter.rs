@@ -0,0 +1,104 @@
+use std::{path::Path, process::Stdio, sync::Arc, time::Duration as StdDuration};
+
+use anyhow::{Context as _, Result as AnyhowResult, bail};
+use bytes::Bytes;
+use libvips::VipsImage;
+use tokio::{fs, process::Command, task, time};
+use uuid::Uuid;
+
+use crate::schemas::enums::FileType;
+
+const DOCUMENT_CONVERSION_TIMEOUT: StdDuration = StdDuration::from_secs(60);
+
+/// Converts uploaded files which cannot be printed as-is into their printable file type, see
+/// [`FileType::printable`]. Images are converted with `libvips`, while documents are converted by
+/// a LibreOffice-compatible office converter.
+#[derive(Clone, Debug)]
+pub struct Converter {
+    office_converter: Arc<str>,
+}
+
+impl Converter {
+    #[must_use]
+    pub(super) fn new(office_converter: &str) -> Self {
+        Self {
+            office_converter: Arc::from(office_converter),
+        }
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    pub async fn convert(&self, buffer: Bytes, filetype: FileType) -> AnyhowResult<Bytes> {
+        match filetype {
+            FileType::Docx => self.convert_document(buffer, filetype).await,
+            FileType::Heic | FileType::Webp => {
+                let suffix = format!(".{}", filetype.printable());
+                task::spawn_blocking(move || {
+                    let vips_image = VipsImage::new_from_buffer(&buffer, "")
+                        .context("Failed to read the image")?;
+
+                    Ok(vips_image
+                        .image_write_to_buffer(&suffix)
+                        .context("Failed to write the converted image")?
+                        .into())
+                })
+                .await?
+            }
+            _ => Ok(buffer),
+        }
+    }
+
+    async fn convert_document(&self, buffer: Bytes, filetype: FileType) -> AnyhowResult<Bytes> {
+        let directory = std::env::temp_dir().join(format!("graphein-{}", Uuid::new_v4()));
+        fs::create_dir_all(&directory).await?;
+        let converted = self
+            .run_office_converter(&directory, buffer, filetype)
+            .await;
+        fs::remove_dir_all(&directory).await.ok();
+
+        converted
+    }
+
+    async fn run_office_converter(
+        &self,
+        directory: &Path,
+        buffer: Bytes,
+        filetype: FileType,
+    ) -> AnyhowResult<Bytes> {
+        let printable = filetype.printable();
+        let input = directory.join(format!("input.{filetype}"));
+        fs::write(&input, buffer).await?;
+
+        // Each conversion gets its own profile, as the converter refuses to run concurrently on a
+        // shared one
+        let output = time::timeout(
+            DOCUMENT_CONVERSION_TIMEOUT,
+            Command::new(&*self.office_converter)
+                .arg(format!(
+                    "-env:UserInstallation=file://{}",
+                    directory.join("profile").display(),
+                ))
+                .args(["--headless", "--convert-to", &printable.to_string()])
+                .arg("--outdir")
+                .arg(directory)
+                .arg(&input)
+                .stdin(Stdio::null())
+                .kill_on_drop(true)
+                .output(),
+        )
+        .await
+        .context("The office converter timed out")?
+        .context("Failed to run the office converter")?;
+        if !output.status.success() {
+            bail!(
+                "The office converter exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim(),
+            );
+        }
+
+        Ok(fs::read(directory.join(format!("input.{printable}")))
+            .await
+            .context("The office converter did not produce any output")?
+            .into())
+    }
+}
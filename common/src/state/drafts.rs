@@ -1,40 +1,50 @@
-use std::sync::{
-    Arc,
-    atomic::{AtomicU16, Ordering},
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        Arc,
+        atomic::{AtomicU16, Ordering},
+    },
 };
 
+use anyhow::Result as AnyhowResult;
 use chrono::{DateTime, Utc};
-use futures::stream::{self, TryStreamExt as _};
+use futures::stream::{self, StreamExt as _, TryStreamExt as _};
 use rand::{RngCore as _, SeedableRng as _, rngs::StdRng};
 use scc::{HashMap as SccMap, hash_map::OccupiedEntry};
+use sqlx::{PgConnection, PgPool};
+use tokio::task;
 use uuid::Uuid;
 
 use crate::{
-    AppError, MAX_FILE_LIMIT,
+    AppError, MAX_FILE_LIMIT, SqlxResult,
+    database::{PricingTable, ServiceCatalogue, ServicesTable},
     error::{BadRequestError, NotFoundError},
     schemas::{
-        DetailedOrder, File, FileId, FileRange, OrderCreate, OrderId, UserId,
-        enums::{FileType, OrderStatus},
+        DetailedOrder, File, FileCreate, FileId, FileRange, OrderCreate, OrderId, PageRange,
+        Service, UserId,
+        enums::{FileType, OrderStatus, ServiceType},
     },
 };
 
-use super::R2Bucket;
+use super::{Bucket, Converter, Thumbnailer};
 
 const MAX_QUEUE_SEQ: u16 = 25974; /* 26 * 999 */
-const MAX_FILE_RANGES: usize = 5;
+pub(crate) const MAX_FILE_RANGES: usize = 5;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct DraftFile {
     pub id: FileId,
     pub filetype: FileType,
     pub filesize: u64,
     pub object_key: String,
+    pub upload_id: Option<String>,
 }
 
 #[derive(Debug)]
 pub struct DraftOrder {
     id: OrderId,
     created_at: DateTime<Utc>,
+    active_at: DateTime<Utc>,
     pub files: Vec<DraftFile>,
 }
 
@@ -44,6 +54,7 @@ impl DraftOrder {
         Self {
             id,
             created_at: Utc::now(),
+            active_at: Utc::now(),
             files: Vec::with_capacity(MAX_FILE_LIMIT),
         }
     }
@@ -53,11 +64,6 @@ impl DraftOrder {
         self.files.len()
     }
 
-    #[must_use]
-    fn contains_file(&self, id: FileId) -> bool {
-        self.files.iter().any(|file| file.id == id)
-    }
-
     #[must_use]
     fn add_file(&mut self, id: FileId, filetype: FileType, filesize: u64) -> String {
         let mut object_key = [0u8; 16];
@@ -68,6 +74,7 @@ impl DraftOrder {
             filetype,
             filesize,
             object_key: object_key.clone(),
+            upload_id: None,
         });
 
         object_key
@@ -122,7 +129,7 @@ impl DraftOrderStore {
 
         if draft.files_len() == MAX_FILE_LIMIT {
             return Err(AppError::BadRequest(BadRequestError::MalformedFiles(
-                "This order has already reached the maximum file limit.",
+                "This order has already reached the maximum file limit.".into(),
             )));
         }
 
@@ -151,21 +158,74 @@ impl DraftOrderStore {
     pub async fn get_order(
         &self,
         owner_id: UserId,
-    ) -> Result<OccupiedEntry<UserId, DraftOrder>, AppError> {
+    ) -> Result<OccupiedEntry<'_, UserId, DraftOrder>, AppError> {
         self.orders
             .get_async(&owner_id)
             .await
             .ok_or(AppError::NotFound(NotFoundError::ResourceNotFound))
     }
 
+    /// Returns the object key, file type, size and upload ID of a draft file which is still being
+    /// uploaded in parts.
     #[tracing::instrument(skip_all, err)]
-    pub async fn get_created_at(&self, owner_id: UserId) -> Result<DateTime<Utc>, AppError> {
+    pub async fn get_upload(
+        &self,
+        owner_id: UserId,
+        file_id: FileId,
+    ) -> Result<(String, FileType, u64, String), AppError> {
         self.orders
-            .read_async(&owner_id, |_, draft| draft.created_at)
+            .read_async(&owner_id, |_, draft| {
+                draft
+                    .files
+                    .iter()
+                    .find(|file| file.id == file_id)
+                    .and_then(|file| {
+                        Some((
+                            file.object_key.clone(),
+                            file.filetype,
+                            file.filesize,
+                            file.upload_id.clone()?,
+                        ))
+                    })
+            })
             .await
+            .flatten()
             .ok_or(AppError::NotFound(NotFoundError::ResourceNotFound))
     }
 
+    /// Keeps a draft order with a long-running upload from being cleared as expired.
+    #[tracing::instrument(skip_all, err)]
+    pub async fn touch(&self, owner_id: UserId) -> Result<(), AppError> {
+        self.orders
+            .update_async(&owner_id, |_, draft| draft.active_at = Utc::now())
+            .await
+            .ok_or(AppError::NotFound(NotFoundError::ResourceNotFound))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    pub async fn set_upload_id(
+        &self,
+        owner_id: UserId,
+        file_id: FileId,
+        upload_id: Option<String>,
+    ) -> Result<(), AppError> {
+        let mut draft = self
+            .orders
+            .get_async(&owner_id)
+            .await
+            .ok_or(AppError::NotFound(NotFoundError::ResourceNotFound))?;
+
+        draft
+            .files
+            .iter_mut()
+            .find(|file| file.id == file_id)
+            .ok_or(AppError::NotFound(NotFoundError::ResourceNotFound))?
+            .upload_id = upload_id;
+        draft.active_at = Utc::now();
+
+        Ok(())
+    }
+
     #[tracing::instrument(skip_all, err)]
     pub async fn remove_file(&self, owner_id: UserId, file_id: FileId) -> Result<(), AppError> {
         let mut draft = self
@@ -183,58 +243,104 @@ impl DraftOrderStore {
         self.orders.remove_async(&owner_id).await.is_some()
     }
 
+    /// Builds a draft order into an order which is ready to be created. Uploaded files are
+    /// inspected without holding on to the draft order or a database connection, after which the
+    /// draft order is removed if it was left untouched in the meantime.
     #[allow(clippy::cast_possible_wrap)]
     #[tracing::instrument(skip_all, err)]
     pub async fn build(
         &self,
-        bucket: &R2Bucket,
+        bucket: &Bucket,
+        converter: &Converter,
+        pool: &PgPool,
         owner_id: UserId,
         OrderCreate {
             notes,
-            files,
+            mut files,
             services,
         }: OrderCreate,
     ) -> Result<DetailedOrder, AppError> {
-        let mut draft_order = self
+        let (order_id, created_at, mut draft_files) = self
             .orders
-            .get_async(&owner_id)
+            .read_async(&owner_id, |_, draft| {
+                (draft.id, draft.created_at, draft.files.clone())
+            })
             .await
             .ok_or(AppError::NotFound(NotFoundError::ResourceNotFound))?;
 
-        if draft_order.files_len() == 0 {
+        if draft_files.is_empty() {
             return Err(AppError::BadRequest(BadRequestError::MalformedFiles(
-                "There are no files present in this order.",
+                "There are no files present in this order.".into(),
             )));
         }
 
+        let contains_file = |id| draft_files.iter().any(|draft_file| draft_file.id == id);
         if !files.iter().all(|file| {
-            draft_order.contains_file(file.id)
+            contains_file(file.id)
                 && !file.ranges.is_empty()
                 && file.ranges.len() <= MAX_FILE_RANGES
         }) || !services.iter().all(|service| {
             service
                 .file_ids
                 .iter()
-                .all(|file_id| draft_order.contains_file(*file_id))
+                .all(|file_id| contains_file(*file_id))
         }) {
             return Err(AppError::BadRequest(BadRequestError::MalformedJson(
                 "Request data contains malformed data for files and/or services".into(),
             )));
         }
 
-        if stream::iter(draft_order.files.iter().map(Ok))
-            .try_for_each_concurrent(MAX_FILE_LIMIT, |draft_file| {
-                bucket.exists(&draft_file.object_key, draft_file.filetype)
-            })
-            .await
-            .is_err()
+        if let Some(draft_file) = draft_files
+            .iter()
+            .find(|draft_file| draft_file.upload_id.is_some())
         {
+            let filename = files
+                .iter()
+                .find(|file| file.id == draft_file.id)
+                .map_or(draft_file.object_key.as_str(), |file| {
+                    file.filename.as_str()
+                });
+
             return Err(AppError::BadRequest(BadRequestError::MalformedFiles(
-                "Object(s) bound to the order were not provided.",
+                format!("File `{filename}` has not finished uploading.").into(),
             )));
         }
 
-        draft_order.files.reverse();
+        Self::validate_contents(&mut *pool.acquire().await?, &mut files, &services).await?;
+
+        let filenames = files
+            .iter()
+            .map(|file| (file.id, file.filename.as_str()))
+            .collect::<HashMap<_, _>>();
+        let page_counts = stream::iter(
+            draft_files
+                .iter()
+                .map(|draft_file| {
+                    (
+                        filenames
+                            .get(&draft_file.id)
+                            .map_or(draft_file.object_key.clone(), ToString::to_string),
+                        draft_file.clone(),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )
+        .map(|(filename, draft_file)| async move {
+            Ok::<_, AppError>((
+                draft_file.id,
+                Self::inspect_file(bucket, converter, &filename, &draft_file).await?,
+            ))
+        })
+        .buffer_unordered(MAX_FILE_LIMIT)
+        .try_collect::<HashMap<_, _>>()
+        .await?;
+        Self::check_page_ranges(&files, &page_counts)?;
+
+        let file_ids = draft_files
+            .iter()
+            .map(|draft_file| draft_file.id)
+            .collect::<HashSet<_>>();
+        draft_files.reverse();
         let files = files
             .into_iter()
             .map(|file| {
@@ -243,8 +349,7 @@ impl DraftOrderStore {
                     filesize,
                     object_key,
                     ..
-                } = draft_order
-                    .files
+                } = draft_files
                     .pop_if(|draft_file| draft_file.id == file.id)
                     .unwrap(); // Infallible
 
@@ -253,32 +358,44 @@ impl DraftOrderStore {
                     filename: file.filename,
                     filetype,
                     filesize: filesize as i64,
+                    page_count: Some(page_counts[&file.id] as i32),
                     object_key,
-                    ranges: file
-                        .ranges
-                        .into_iter()
-                        .map(|file_range| FileRange {
-                            id: Uuid::new_v4().into(),
-                            range: file_range.range,
-                            copies: file_range.copies,
-                            paper_variant_id: Some(file_range.paper_variant_id),
-                            paper_orientation: file_range.paper_orientation,
-                            is_colour: file_range.is_colour,
-                            is_double_sided: file_range.is_double_sided,
-                        })
-                        .collect(),
+                    is_purged: false,
+                    ranges: file.ranges.into_iter().map(FileRange::from).collect(),
                 }
             })
-            .collect();
+            .collect::<Vec<_>>();
+
+        let quote = PricingTable::fetch_rates(&mut *pool.acquire().await?)
+            .await?
+            .quote(&files, &services);
+
+        // Files may have been added, removed or re-uploaded while the draft order was not held
+        let draft_order = self
+            .orders
+            .get_async(&owner_id)
+            .await
+            .ok_or(AppError::NotFound(NotFoundError::ResourceNotFound))?;
+        if draft_order.id != order_id
+            || draft_order.files_len() != file_ids.len()
+            || !draft_order.files.iter().all(|draft_file| {
+                file_ids.contains(&draft_file.id) && draft_file.upload_id.is_none()
+            })
+        {
+            return Err(AppError::BadRequest(BadRequestError::MalformedFiles(
+                "The files of this order changed while it was being built.".into(),
+            )));
+        }
 
         let order = DetailedOrder {
-            id: draft_order.id,
-            created_at: draft_order.created_at,
+            id: order_id,
+            created_at,
             owner_id: Some(owner_id),
             owner: None,
             order_number: Self::convert_queue_seq_to_order_number(self.next_queue()),
             status: OrderStatus::Reviewing,
             price: None,
+            quote,
             notes,
             status_history: Vec::with_capacity(0),
             files,
@@ -289,17 +406,429 @@ impl DraftOrderStore {
         Ok(order)
     }
 
-    pub(crate) async fn clear_expired(&self, bucket: &R2Bucket) {
+    /// Checks that an uploaded file matches what was declared when it was added to the draft
+    /// order, returning its page count. Only files which are converted or have their pages
+    /// counted are downloaded in full.
+    #[tracing::instrument(skip_all, err)]
+    async fn inspect_file(
+        bucket: &Bucket,
+        converter: &Converter,
+        filename: &str,
+        DraftFile {
+            filetype,
+            filesize,
+            object_key,
+            ..
+        }: &DraftFile,
+    ) -> Result<u32, AppError> {
+        let filetype = *filetype;
+        let malformed = |reason: &str| {
+            AppError::BadRequest(BadRequestError::MalformedFiles(
+                format!("File `{filename}` {reason}.").into(),
+            ))
+        };
+        let not_uploaded = |err| match err {
+            AppError::NotFound(_) => malformed("was not uploaded"),
+            err => err,
+        };
+
+        if bucket
+            .get_file_size(object_key, filetype)
+            .await
+            .map_err(not_uploaded)?
+            != *filesize
+        {
+            return Err(malformed("does not match its declared size"));
+        }
+
+        let is_whole_file_needed =
+            filetype.printable() != filetype || matches!(filetype, FileType::Pdf);
+        let buffer = if is_whole_file_needed {
+            bucket.get_file(object_key, filetype).await
+        } else {
+            bucket.get_file_header(object_key, filetype).await
+        }
+        .map_err(not_uploaded)?;
+        if FileType::sniff(&buffer).is_none_or(|sniffed| sniffed != filetype) {
+            return Err(malformed("does not match its declared file type"));
+        }
+        if !is_whole_file_needed {
+            return Ok(1);
+        }
+        // Encrypted PDFs always carry an `/Encrypt` entry in their trailer
+        if matches!(filetype, FileType::Pdf)
+            && buffer.windows(8).any(|window| window == b"/Encrypt")
+        {
+            return Err(malformed("is encrypted or password-protected"));
+        }
+
+        let buffer = if filetype.printable() == filetype {
+            buffer
+        } else {
+            let converted = converter
+                .convert(buffer, filetype)
+                .await
+                .map_err(|_| malformed("could not be converted into a printable file"))?;
+            bucket
+                .put_converted_file(converted.clone(), object_key, filetype)
+                .await?;
+
+            converted
+        };
+        if !matches!(filetype.printable(), FileType::Pdf) {
+            return Ok(1);
+        }
+
+        task::spawn_blocking(move || Thumbnailer::count_pages(&buffer))
+            .await
+            .map_err(anyhow::Error::from)?
+            .map_err(|_| malformed("could not be read"))
+    }
+
+    /// Normalises the page ranges of the files, then checks them and the services against the
+    /// service catalogue.
+    pub(crate) async fn validate_contents(
+        conn: &mut PgConnection,
+        files: &mut [FileCreate],
+        services: &[Service],
+    ) -> Result<(), AppError> {
+        Self::normalise_ranges(files)?;
+
+        let paper_variant_ids = files
+            .iter()
+            .flat_map(|file| {
+                file.ranges
+                    .iter()
+                    .map(|file_range| file_range.paper_variant_id)
+            })
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let binding_colour_ids = services
+            .iter()
+            .filter_map(|service| service.binding_colour_id)
+            .collect::<Vec<_>>();
+        let lamination_film_ids = services
+            .iter()
+            .filter_map(|service| service.lamination_film_id)
+            .collect::<Vec<_>>();
+        let catalogue = ServicesTable::fetch_catalogue(
+            conn,
+            &paper_variant_ids,
+            &binding_colour_ids,
+            &lamination_film_ids,
+        )
+        .await?;
+        Self::validate_services(&catalogue, files, services)?;
+
+        Ok(())
+    }
+
+    /// Rejects page ranges which go beyond the last page of their file.
+    pub(crate) fn check_page_ranges(
+        files: &[FileCreate],
+        page_counts: &HashMap<FileId, u32>,
+    ) -> Result<(), AppError> {
+        if !files.iter().all(|file| {
+            file.ranges.iter().all(|file_range| {
+                file_range.range.as_deref().is_none_or(|range| {
+                    range
+                        .parse::<PageRange>()
+                        .is_ok_and(|page_range| page_range.fits_within(page_counts[&file.id]))
+                })
+            })
+        }) {
+            return Err(AppError::BadRequest(BadRequestError::MalformedJson(
+                "Request data contains page ranges beyond the last page of a file".into(),
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Rewrites the page ranges of every file into their canonical form, rejecting malformed page
+    /// ranges and page ranges which overlap with another page range of the same file.
+    fn normalise_ranges(files: &mut [FileCreate]) -> Result<(), AppError> {
+        for file in files {
+            let ranges_len = file.ranges.len();
+            let mut page_ranges: Vec<PageRange> = Vec::with_capacity(ranges_len);
+            for file_range in &mut file.ranges {
+                let Some(range) = file_range.range.as_deref() else {
+                    // A missing page range covers the whole file
+                    if ranges_len == 1 {
+                        continue;
+                    }
+
+                    return Err(AppError::BadRequest(BadRequestError::MalformedJson(
+                        "Request data contains overlapping page ranges".into(),
+                    )));
+                };
+
+                let page_range = range.parse::<PageRange>().map_err(|_| {
+                    AppError::BadRequest(BadRequestError::MalformedJson(
+                        "Request data contains malformed page ranges".into(),
+                    ))
+                })?;
+                if page_ranges.iter().any(|other| other.overlaps(&page_range)) {
+                    return Err(AppError::BadRequest(BadRequestError::MalformedJson(
+                        "Request data contains overlapping page ranges".into(),
+                    )));
+                }
+
+                file_range.range = Some(page_range.to_string());
+                page_ranges.push(page_range);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks the requested services against the service catalogue, rejecting services which the
+    /// shop cannot currently fulfil.
+    fn validate_services(
+        catalogue: &ServiceCatalogue,
+        files: &[FileCreate],
+        services: &[Service],
+    ) -> Result<(), AppError> {
+        let malformed = || {
+            AppError::BadRequest(BadRequestError::MalformedJson(
+                "Request data contains malformed data for files and/or services".into(),
+            ))
+        };
+
+        if !files.iter().all(|file| {
+            file.ranges.iter().all(|file_range| {
+                catalogue
+                    .paper_variants
+                    .contains_key(&file_range.paper_variant_id)
+            })
+        }) {
+            return Err(malformed());
+        }
+
+        for service in services {
+            let mut paper_variants = service
+                .file_ids
+                .iter()
+                .filter_map(|file_id| files.iter().find(|file| file.id == *file_id))
+                .flat_map(|file| file.ranges.iter())
+                .map(|file_range| catalogue.paper_variants[&file_range.paper_variant_id]);
+
+            match service.r#type {
+                ServiceType::Binding | ServiceType::BindingWithCover => {
+                    if service.lamination_film_id.is_some() {
+                        return Err(malformed());
+                    }
+
+                    let (binding_id, is_available) = catalogue
+                        .binding_colours
+                        .get(&service.binding_colour_id.ok_or_else(malformed)?)
+                        .copied()
+                        .ok_or(AppError::BadRequest(
+                            BadRequestError::UnavailableBindingColour,
+                        ))?;
+                    if !is_available {
+                        return Err(AppError::BadRequest(
+                            BadRequestError::UnavailableBindingColour,
+                        ));
+                    }
+
+                    let requires_cover = matches!(service.r#type, ServiceType::BindingWithCover);
+                    if !paper_variants.all(|(paper_id, _)| {
+                        catalogue
+                            .bindings_papers
+                            .get(&(binding_id, paper_id))
+                            .is_some_and(|coverable| !requires_cover || *coverable)
+                    }) {
+                        return Err(AppError::BadRequest(BadRequestError::IncompatibleBinding));
+                    }
+                }
+                ServiceType::Laminate => {
+                    if service.binding_colour_id.is_some() {
+                        return Err(malformed());
+                    }
+
+                    if !catalogue.is_lamination_serviceable
+                        || !paper_variants.all(|(_, is_laminatable)| is_laminatable)
+                        || service.lamination_film_id.is_some_and(|film_id| {
+                            !catalogue
+                                .lamination_films
+                                .get(&film_id)
+                                .copied()
+                                .unwrap_or(false)
+                        })
+                    {
+                        return Err(AppError::BadRequest(BadRequestError::UnavailableLamination));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the object keys of every file in every draft order.
+    pub(crate) async fn object_keys(&self) -> Vec<String> {
+        let mut object_keys = Vec::new();
+        self.orders
+            .scan_async(|_, draft| {
+                object_keys.extend(draft.files.iter().map(|file| file.object_key.clone()));
+            })
+            .await;
+
+        object_keys
+    }
+
+    /// Restores the draft orders committed to the database by [`DraftOrderStore::commit`].
+    #[allow(clippy::cast_sign_loss)]
+    #[tracing::instrument(skip_all, err)]
+    pub async fn load(&self, pool: PgPool) -> AnyhowResult<()> {
+        let draft_orders: Vec<(UserId, OrderId, DateTime<Utc>, DateTime<Utc>)> =
+            sqlx::query_as("SELECT owner_id, id, created_at, active_at FROM draft_orders")
+                .fetch_all(&pool)
+                .await?;
+        let draft_files: Vec<(UserId, FileId, FileType, i64, String, Option<String>)> =
+            sqlx::query_as(
+                "\
+                SELECT owner_id, id, filetype, filesize, object_key, upload_id FROM draft_files \
+                ORDER BY owner_id, index\
+                ",
+            )
+            .fetch_all(&pool)
+            .await?;
+
+        let mut files = HashMap::<_, Vec<_>>::new();
+        for (owner_id, id, filetype, filesize, object_key, upload_id) in draft_files {
+            files.entry(owner_id).or_default().push(DraftFile {
+                id,
+                filetype,
+                filesize: filesize as u64,
+                object_key,
+                upload_id,
+            });
+        }
+        for (owner_id, id, created_at, active_at) in draft_orders {
+            self.orders
+                .insert_async(
+                    owner_id,
+                    DraftOrder {
+                        id,
+                        created_at,
+                        active_at,
+                        files: files.remove(&owner_id).unwrap_or_default(),
+                    },
+                )
+                .await
+                .ok();
+        }
+
+        sqlx::query("TRUNCATE draft_files, draft_orders")
+            .execute(&pool)
+            .await?;
+
+        tracing::debug!("loaded {} draft order(s) from database", self.orders.len());
+        Ok(())
+    }
+
+    /// Commits all the draft orders in the store into the database, so that they and their
+    /// uploaded files survive a restart.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    #[tracing::instrument(skip_all, err)]
+    pub async fn commit(&self, pool: PgPool) -> SqlxResult<()> {
+        let store_len = self.orders.len();
+        let mut owner_ids = Vec::with_capacity(store_len);
+        let mut order_ids = Vec::with_capacity(store_len);
+        let mut created_ats = Vec::with_capacity(store_len);
+        let mut active_ats = Vec::with_capacity(store_len);
+        let mut file_ids = Vec::new();
+        let mut file_owner_ids = Vec::new();
+        let mut filetypes = Vec::new();
+        let mut filesizes = Vec::new();
+        let mut object_keys = Vec::new();
+        let mut upload_ids = Vec::new();
+        let mut indexes = Vec::new();
+        self.orders
+            .scan_async(|owner_id, draft| {
+                owner_ids.push(*owner_id);
+                order_ids.push(draft.id);
+                created_ats.push(draft.created_at);
+                active_ats.push(draft.active_at);
+                draft.files.iter().enumerate().for_each(|(index, file)| {
+                    file_ids.push(file.id);
+                    file_owner_ids.push(*owner_id);
+                    filetypes.push(file.filetype);
+                    filesizes.push(file.filesize as i64);
+                    object_keys.push(file.object_key.clone());
+                    upload_ids.push(file.upload_id.clone());
+                    indexes.push(index as i32);
+                });
+            })
+            .await;
+
+        let mut tx = pool.begin().await?;
+        let rows_affected = sqlx::query(
+            "\
+            INSERT INTO draft_orders (owner_id, id, created_at, active_at) \
+            SELECT * FROM UNNEST(\
+                $1::uuid[],\
+                $2::uuid[],\
+                $3::timestamp with time zone[],\
+                $4::timestamp with time zone[]\
+            )\
+            ",
+        )
+        .bind(&owner_ids[..])
+        .bind(&order_ids[..])
+        .bind(&created_ats[..])
+        .bind(&active_ats[..])
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        sqlx::query(
+            "\
+            INSERT INTO draft_files (\
+                id, owner_id, filetype, filesize, object_key, upload_id, index\
+            ) SELECT * FROM UNNEST(\
+                $1::uuid[], $2::uuid[], $3::filetype[], $4::bigint[], $5::text[], $6::text[],\
+                $7::integer[]\
+            )\
+            ",
+        )
+        .bind(&file_ids[..])
+        .bind(&file_owner_ids[..])
+        .bind(&filetypes[..])
+        .bind(&filesizes[..])
+        .bind(&object_keys[..])
+        .bind(&upload_ids[..])
+        .bind(&indexes[..])
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        tracing::debug!("committed {rows_affected} draft order(s) to database");
+
+        Ok(())
+    }
+
+    pub(crate) async fn clear_expired(&self, bucket: &Bucket) {
         let now = Utc::now();
         let mut expired_files = Vec::new();
+        let mut expired_uploads = Vec::new();
         self.orders
             .retain_async(|_, draft| {
-                if (now - draft.created_at).num_minutes() <= 15 {
+                if (now - draft.active_at).num_minutes() <= 15 {
                     true
                 } else {
                     tracing::warn!("clearing draft order `{:?}`", draft.id);
                     draft.files.iter().for_each(|draft_file| {
                         expired_files.push((draft_file.object_key.clone(), draft_file.filetype));
+                        if let Some(upload_id) = &draft_file.upload_id {
+                            expired_uploads.push((
+                                draft_file.object_key.clone(),
+                                draft_file.filetype,
+                                upload_id.clone(),
+                            ));
+                        }
                     });
 
                     false
@@ -307,6 +836,12 @@ impl DraftOrderStore {
             })
             .await;
 
+        for (object_key, filetype, upload_id) in expired_uploads {
+            bucket
+                .abort_multipart_upload(&object_key, filetype, &upload_id)
+                .await
+                .ok();
+        }
         if !expired_files.is_empty() {
             bucket.delete_files(&expired_files).await.ok();
         }
@@ -347,3 +882,188 @@ impl DraftOrderStore {
         format!("{alphabet}-{number:03}")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use uuid::Uuid;
+
+    use super::DraftOrderStore;
+    use crate::{
+        AppError,
+        database::ServiceCatalogue,
+        error::BadRequestError,
+        schemas::{
+            BindingColourId, BindingId, FileCreate, FileId, FileRangeCreate, LaminationFilmId,
+            PaperId, PaperVariantId, Service,
+            enums::{PaperOrientation, ServiceType},
+        },
+    };
+
+    /// Paper variant 1 is laminatable and bound by either binding, with a cover only by binding
+    /// 1, while paper variant 2 is neither laminatable nor bound by any binding. Binding colour 2
+    /// and lamination film 2 are unavailable.
+    fn catalogue() -> ServiceCatalogue {
+        ServiceCatalogue {
+            is_lamination_serviceable: true,
+            paper_variants: HashMap::from([
+                (PaperVariantId::from(1), (PaperId::from(1), true)),
+                (PaperVariantId::from(2), (PaperId::from(2), false)),
+            ]),
+            binding_colours: HashMap::from([
+                (BindingColourId::from(1), (BindingId::from(1), true)),
+                (BindingColourId::from(2), (BindingId::from(1), false)),
+                (BindingColourId::from(3), (BindingId::from(2), true)),
+            ]),
+            bindings_papers: HashMap::from([
+                ((BindingId::from(1), PaperId::from(1)), true),
+                ((BindingId::from(2), PaperId::from(1)), false),
+            ]),
+            lamination_films: HashMap::from([
+                (LaminationFilmId::from(1), true),
+                (LaminationFilmId::from(2), false),
+            ]),
+        }
+    }
+
+    /// File 1 is printed on paper variant 1 and file 2 on paper variant 2.
+    fn files() -> Vec<FileCreate> {
+        [1, 2]
+            .into_iter()
+            .map(|id| FileCreate {
+                id: FileId::from(Uuid::from_u128(id)),
+                filename: String::from("file.pdf"),
+                ranges: vec![FileRangeCreate {
+                    range: None,
+                    copies: 1,
+                    paper_variant_id: PaperVariantId::from(i32::try_from(id).unwrap()),
+                    paper_orientation: PaperOrientation::Portrait,
+                    is_colour: false,
+                    is_double_sided: false,
+                }],
+            })
+            .collect()
+    }
+
+    fn binding(r#type: ServiceType, binding_colour_id: i32, file_id: u128) -> Service {
+        Service {
+            r#type,
+            binding_colour_id: Some(BindingColourId::from(binding_colour_id)),
+            lamination_film_id: None,
+            notes: None,
+            file_ids: vec![FileId::from(Uuid::from_u128(file_id))],
+        }
+    }
+
+    fn lamination(lamination_film_id: Option<i32>, file_id: u128) -> Service {
+        Service {
+            r#type: ServiceType::Laminate,
+            binding_colour_id: None,
+            lamination_film_id: lamination_film_id.map(LaminationFilmId::from),
+            notes: None,
+            file_ids: vec![FileId::from(Uuid::from_u128(file_id))],
+        }
+    }
+
+    fn validate(catalogue: &ServiceCatalogue, service: Service) -> Result<(), AppError> {
+        DraftOrderStore::validate_services(catalogue, &files(), &[service])
+    }
+
+    #[test]
+    fn accepts_services_which_can_be_fulfilled() {
+        let catalogue = catalogue();
+        assert!(validate(&catalogue, binding(ServiceType::Binding, 1, 1)).is_ok());
+        assert!(validate(&catalogue, binding(ServiceType::Binding, 3, 1)).is_ok());
+        assert!(validate(&catalogue, binding(ServiceType::BindingWithCover, 1, 1)).is_ok());
+        assert!(validate(&catalogue, lamination(None, 1)).is_ok());
+        assert!(validate(&catalogue, lamination(Some(1), 1)).is_ok());
+    }
+
+    #[test]
+    fn rejects_unavailable_or_unknown_binding_colours() {
+        for binding_colour_id in [2, 4] {
+            assert!(matches!(
+                validate(
+                    &catalogue(),
+                    binding(ServiceType::Binding, binding_colour_id, 1)
+                ),
+                Err(AppError::BadRequest(
+                    BadRequestError::UnavailableBindingColour
+                ))
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_bindings_on_incompatible_papers() {
+        assert!(matches!(
+            validate(&catalogue(), binding(ServiceType::Binding, 1, 2)),
+            Err(AppError::BadRequest(BadRequestError::IncompatibleBinding))
+        ));
+    }
+
+    #[test]
+    fn rejects_covers_on_bindings_which_are_not_coverable() {
+        assert!(matches!(
+            validate(&catalogue(), binding(ServiceType::BindingWithCover, 3, 1)),
+            Err(AppError::BadRequest(BadRequestError::IncompatibleBinding))
+        ));
+    }
+
+    #[test]
+    fn rejects_lamination_while_it_is_not_serviceable() {
+        let catalogue = ServiceCatalogue {
+            is_lamination_serviceable: false,
+            ..catalogue()
+        };
+        assert!(matches!(
+            validate(&catalogue, lamination(None, 1)),
+            Err(AppError::BadRequest(BadRequestError::UnavailableLamination))
+        ));
+    }
+
+    #[test]
+    fn rejects_lamination_of_paper_variants_which_are_not_laminatable() {
+        assert!(matches!(
+            validate(&catalogue(), lamination(None, 2)),
+            Err(AppError::BadRequest(BadRequestError::UnavailableLamination))
+        ));
+    }
+
+    #[test]
+    fn rejects_unavailable_or_unknown_lamination_films() {
+        for lamination_film_id in [2, 3] {
+            assert!(matches!(
+                validate(&catalogue(), lamination(Some(lamination_film_id), 1)),
+                Err(AppError::BadRequest(BadRequestError::UnavailableLamination))
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_ids_which_do_not_match_the_service_type() {
+        let binding_with_film = Service {
+            lamination_film_id: Some(LaminationFilmId::from(1)),
+            ..binding(ServiceType::Binding, 1, 1)
+        };
+        let binding_without_colour = Service {
+            binding_colour_id: None,
+            ..binding(ServiceType::BindingWithCover, 1, 1)
+        };
+        let lamination_with_colour = Service {
+            binding_colour_id: Some(BindingColourId::from(1)),
+            ..lamination(Some(1), 1)
+        };
+        for service in [
+            binding_with_film,
+            binding_without_colour,
+            lamination_with_colour,
+        ] {
+            assert!(matches!(
+                validate(&catalogue(), service),
+                Err(AppError::BadRequest(BadRequestError::MalformedJson(_)))
+            ));
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use futures::stream::{self, BoxStream, StreamExt as _};
+use tokio::sync::broadcast::{self, Sender, error::RecvError};
+
+use crate::schemas::{IncomingOrderEvent, OrderStatusChange};
+
+pub(crate) const ORDER_STATUS_CHANGES_CHANNEL: &str = "order_status_changes";
+pub(crate) const INCOMING_ORDERS_CHANNEL: &str = "incoming_orders";
+
+const EVENTS_CAPACITY: usize = 64;
+
+#[derive(Clone, Debug)]
+pub struct OrderEvents {
+    status_changes: Sender<OrderStatusChange>,
+    incoming_orders: Sender<IncomingOrderEvent>,
+}
+
+impl OrderEvents {
+    #[must_use]
+    pub(super) fn new() -> Self {
+        Self {
+            status_changes: broadcast::channel(EVENTS_CAPACITY).0,
+            incoming_orders: broadcast::channel(EVENTS_CAPACITY).0,
+        }
+    }
+
+    /// Dispatches a notification received from the `LISTEN`/`NOTIFY` channel of the database to
+    /// every subscriber of the matching event stream.
+    #[tracing::instrument(skip_all, err)]
+    pub(crate) fn dispatch(&self, channel: &str, payload: &str) -> serde_json::Result<()> {
+        // Sending only fails when there are no subscribers, which is fine
+        match channel {
+            ORDER_STATUS_CHANGES_CHANNEL => {
+                self.status_changes
+                    .send(serde_json::from_str(payload)?)
+                    .ok();
+            }
+            INCOMING_ORDERS_CHANNEL => {
+                self.incoming_orders
+                    .send(serde_json::from_str(payload)?)
+                    .ok();
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn subscribe_status_changes(&self) -> BoxStream<'static, OrderStatusChange> {
+        Self::subscribe(&self.status_changes)
+    }
+
+    #[must_use]
+    pub fn subscribe_incoming_orders(&self) -> BoxStream<'static, IncomingOrderEvent> {
+        Self::subscribe(&self.incoming_orders)
+    }
+
+    fn subscribe<T: Clone + Send + 'static>(sender: &Sender<T>) -> BoxStream<'static, T> {
+        stream::unfold(sender.subscribe(), async |mut rx| {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => return Some((event, rx)),
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!("event subscriber lagged behind by {skipped} event(s)");
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+        .boxed()
+    }
+}
@@ -1,6 +1,7 @@
-use std::time::Duration as StdDuration;
+use std::{sync::Arc, time::Duration as StdDuration};
 
 use anyhow::{Context, Result as AnyhowResult, anyhow};
+use chrono::{DateTime, Utc};
 use libvips::{
     VipsImage,
     ops::{
@@ -10,13 +11,20 @@ use libvips::{
         webpsave_buffer_with_opts as vips_webpsave_buffer_with_opts,
     },
 };
+use scc::{HashMap as SccMap, hash_map::Entry};
 use tokio::{
     runtime::Handle,
-    sync::mpsc::{self, Receiver, Sender, error::SendError},
-    time::Instant,
+    sync::mpsc::{self, Receiver, Sender, error::TrySendError},
+    time::{self, Instant},
 };
 
-use crate::{R2Bucket, schemas::enums::FileType};
+use crate::{
+    AppError, Bucket,
+    schemas::{ThumbnailJobStatus, enums::FileType},
+};
+
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_BACKOFF: StdDuration = StdDuration::from_secs(2);
 
 pub(crate) fn vips_version_check(version: &str) -> AnyhowResult<()> {
     let (major, minor) = version
@@ -39,36 +47,230 @@ pub(crate) fn vips_version_check(version: &str) -> AnyhowResult<()> {
 }
 
 #[derive(Clone, Debug)]
-pub struct Thumbnailer(Sender<(String, FileType)>);
+pub struct ThumbnailJob {
+    pub object_key: String,
+    pub filetype: FileType,
+    /// The pages of a PDF to be previewed, starting from 1, which are all rendered from a single
+    /// download of the file. No pages is the thumbnail of the file.
+    pub pages: Vec<u32>,
+    attempts: u32,
+}
+
+impl ThumbnailJob {
+    #[must_use]
+    pub fn new(object_key: String, filetype: FileType) -> Self {
+        Self {
+            object_key,
+            filetype,
+            pages: Vec::new(),
+            attempts: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn with_pages(mut self, pages: Vec<u32>) -> Self {
+        self.pages = pages;
+
+        self
+    }
+
+    #[must_use]
+    pub(crate) fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// The keys which are unique to each thumbnail produced by the job, in the order of its pages.
+    #[must_use]
+    pub(crate) fn thumbnail_keys(&self) -> Vec<String> {
+        if self.pages.is_empty() {
+            return vec![self.object_key.clone()];
+        }
+
+        self.pages
+            .iter()
+            .map(|page| format!("{}.p{page}", self.object_key))
+            .collect()
+    }
+}
+
+type ThumbnailJobStatuses = SccMap<String, (ThumbnailJobStatus, DateTime<Utc>)>;
+
+#[derive(Clone, Debug)]
+pub struct Thumbnailer {
+    tx: Sender<ThumbnailJob>,
+    statuses: Arc<ThumbnailJobStatuses>,
+}
 
 impl Thumbnailer {
     #[must_use]
-    pub fn new() -> (Self, Receiver<(String, FileType)>) {
-        let (tx, rx) = mpsc::channel(10); // Totally an arbitrary number
-        (Self(tx), rx)
+    pub fn new(queue_size: usize) -> (Self, Receiver<ThumbnailJob>) {
+        let (tx, rx) = mpsc::channel(queue_size);
+        (
+            Self {
+                tx,
+                statuses: Arc::new(SccMap::new()),
+            },
+            rx,
+        )
     }
 
+    /// Queues a job for processing and returns the status of each of its thumbnails, in the order
+    /// of its pages. A thumbnail which is already queued, being processed or has failed is not
+    /// queued again until its status expires.
+    ///
+    /// Fails with [`AppError::TimeoutError`] when the queue is full, so that the caller may try
+    /// again later.
     #[tracing::instrument(skip_all, err)]
     pub async fn signal_for_processing(
         &self,
-        object_key: String,
-        filetype: FileType,
-    ) -> Result<(), SendError<(String, FileType)>> {
-        self.0.send((object_key, filetype)).await
+        mut job: ThumbnailJob,
+    ) -> Result<Vec<ThumbnailJobStatus>, AppError> {
+        let thumbnail_keys = job.thumbnail_keys();
+        let mut statuses = Vec::with_capacity(thumbnail_keys.len());
+        let mut is_queued = Vec::with_capacity(thumbnail_keys.len());
+        for thumbnail_key in &thumbnail_keys {
+            match self.statuses.entry_async(thumbnail_key.clone()).await {
+                Entry::Occupied(entry) if !matches!(entry.get().0, ThumbnailJobStatus::Done) => {
+                    statuses.push(entry.get().0.clone());
+                    is_queued.push(false);
+                    continue;
+                }
+                Entry::Occupied(mut entry) => {
+                    *entry.get_mut() = (ThumbnailJobStatus::Queued, Utc::now());
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert_entry((ThumbnailJobStatus::Queued, Utc::now()));
+                }
+            }
+
+            statuses.push(ThumbnailJobStatus::Queued);
+            is_queued.push(true);
+        }
+        if !is_queued.contains(&true) {
+            return Ok(statuses);
+        }
+
+        // Only the pages which are not already tracked are rendered again
+        if !job.pages.is_empty() {
+            job.pages = job
+                .pages
+                .into_iter()
+                .zip(&is_queued)
+                .filter_map(|(page, is_queued)| is_queued.then_some(page))
+                .collect();
+        }
+        if let Err(err) = self.tx.try_send(job) {
+            for (thumbnail_key, is_queued) in thumbnail_keys.iter().zip(is_queued) {
+                if is_queued {
+                    self.statuses.remove_async(thumbnail_key).await;
+                }
+            }
+            return Err(match err {
+                TrySendError::Full(_) => {
+                    tracing::warn!(
+                        queue_depth = self.queue_depth(),
+                        "thumbnailer queue is full"
+                    );
+                    AppError::TimeoutError
+                }
+                TrySendError::Closed(_) => AppError::InternalServerError(anyhow!("Channel closed")),
+            });
+        }
+
+        Ok(statuses)
+    }
+
+    /// The number of jobs which are waiting for a worker.
+    #[must_use]
+    pub fn queue_depth(&self) -> usize {
+        self.tx.max_capacity() - self.tx.capacity()
+    }
+
+    #[must_use]
+    pub fn queue_size(&self) -> usize {
+        self.tx.max_capacity()
+    }
+
+    /// Queues a failed job again after a backoff which doubles on every attempt. Gives the job
+    /// back once it has exhausted all of its attempts.
+    pub(crate) fn schedule_retry(
+        &self,
+        handle: &Handle,
+        mut job: ThumbnailJob,
+    ) -> Result<StdDuration, ThumbnailJob> {
+        job.attempts += 1;
+        if job.attempts >= MAX_ATTEMPTS {
+            return Err(job);
+        }
+
+        let backoff = RETRY_BACKOFF * 2u32.pow(job.attempts - 1);
+        let tx = self.tx.clone();
+        handle.spawn(async move {
+            time::sleep(backoff).await;
+            tx.send(job).await.ok();
+        });
+
+        Ok(backoff)
     }
 
+    pub(crate) fn set_status(&self, thumbnail_key: String, status: ThumbnailJobStatus) {
+        self.statuses.upsert(thumbnail_key, (status, Utc::now()));
+    }
+
+    /// Forgets the status of finished jobs after an hour, so that failed jobs may be retried.
+    pub(crate) fn clear_expired_statuses(&self) {
+        let now = Utc::now();
+        self.statuses.retain(|_, (status, updated_at)| {
+            matches!(
+                status,
+                ThumbnailJobStatus::Queued | ThumbnailJobStatus::Processing
+            ) || now.signed_duration_since(*updated_at).num_hours() < 1
+        });
+    }
+
+    /// Reads the page count of a document, which is always 1 for images.
     #[tracing::instrument(skip_all, err)]
-    pub(crate) fn process_single_thumbnail(
+    pub(crate) fn count_pages(buffer: &[u8]) -> AnyhowResult<u32> {
+        let vips_image = VipsImage::new_from_buffer(buffer, "")?;
+
+        Ok(u32::try_from(vips_image.get_n_pages())?)
+    }
+
+    /// Renders every thumbnail of a job from a single download of its file.
+    #[tracing::instrument(skip_all, err)]
+    pub(crate) fn process_thumbnails(
         handle: &Handle,
-        bucket: &R2Bucket,
+        bucket: &Bucket,
         size: i32,
-        object_key: &str,
-        filetype: FileType,
+        job: &ThumbnailJob,
     ) -> AnyhowResult<StdDuration> {
         let time = Instant::now();
-        let buffer =
-            handle.block_on(bucket.get_file_for_thumbnail_processing(object_key, filetype))?;
-        let vips_image = VipsImage::new_from_buffer(&buffer, "")?;
+        let buffer = handle
+            .block_on(bucket.get_file_for_thumbnail_processing(&job.object_key, job.filetype))
+            .context("Failed to download the file")?;
+        if job.pages.is_empty() {
+            Self::process_single_thumbnail(handle, bucket, size, job, &buffer, None)?;
+        }
+        for page in &job.pages {
+            Self::process_single_thumbnail(handle, bucket, size, job, &buffer, Some(*page))?;
+        }
+
+        Ok(time.elapsed())
+    }
+
+    fn process_single_thumbnail(
+        handle: &Handle,
+        bucket: &Bucket,
+        size: i32,
+        job: &ThumbnailJob,
+        buffer: &[u8],
+        page: Option<u32>,
+    ) -> AnyhowResult<()> {
+        let options = page
+            .map(|page| format!("page={}", page.saturating_sub(1)))
+            .unwrap_or_default();
+        let vips_image =
+            VipsImage::new_from_buffer(buffer, &options).context("Failed to read the file")?;
         let vips_image_thumbnail = vips_thumbnail_image_with_opts(
             &vips_image,
             size,
@@ -78,7 +280,8 @@ impl Thumbnailer {
                 export_profile: String::from("sRGB"),
                 ..Default::default()
             },
-        )?;
+        )
+        .context("Failed to render the thumbnail")?;
 
         let thumbnail_buffer = vips_webpsave_buffer_with_opts(
             &vips_image_thumbnail,
@@ -89,9 +292,12 @@ impl Thumbnailer {
                 profile: String::from("none"),
                 ..Default::default()
             },
-        )?;
-        handle.block_on(bucket.put_thumbnail(thumbnail_buffer.into(), object_key))?;
+        )
+        .context("Failed to encode the thumbnail")?;
+        handle
+            .block_on(bucket.put_thumbnail(thumbnail_buffer.into(), &job.object_key, page))
+            .context("Failed to upload the thumbnail")?;
 
-        Ok(time.elapsed())
+        Ok(())
     }
 }
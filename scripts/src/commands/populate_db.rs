@@ -29,11 +29,9 @@ pub(super) async fn run(database_url: String, args: Args) -> AnyhowResult<()> {
 
     sqlx::query!(
         "\
-        INSERT INTO settings (is_accepting, is_lamination_serviceable, open_time, close_time)\
-        VALUES ($1, $2, $3, $4)\
+        INSERT INTO settings (is_accepting, open_time, close_time) VALUES ($1, $2, $3)\
         ",
         true,
-        false,
         args.open_time,
         args.close_time,
     )
@@ -52,19 +50,22 @@ pub(super) async fn run(database_url: String, args: Args) -> AnyhowResult<()> {
     .fetch_one(&mut *tx)
     .await
     .context("Failed while trying to create paper")?;
-    sqlx::query!(
-        "\
-        INSERT INTO paper_variants (paper_id, name, is_default, is_laminatable)\
-        VALUES ($1, $2, $3, $4)\
-        ",
+    let paper_variant_id = sqlx::query_scalar!(
+        "INSERT INTO paper_variants (paper_id, name, is_default) VALUES ($1, $2, $3) RETURNING id",
         paper_id,
         "Standard Copy Paper (80 gsm)",
         true,
-        true,
     )
-    .execute(&mut *tx)
+    .fetch_one(&mut *tx)
     .await
     .context("Failed while trying to create paper variant")?;
+    sqlx::query!(
+        "INSERT INTO lamination_paper_variants (paper_variant_id) VALUES ($1)",
+        paper_variant_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .context("Failed while trying to make paper variant laminatable")?;
     println!("Created a default paper size and variant");
 
     let merchant_id = sqlx::query_scalar!(
@@ -122,7 +122,6 @@ export type PaperVariant = {
   name: string;
   isDefault: boolean;
   isAvailable: boolean;
-  isLaminatable: boolean;
 };
 
 type BaseService = { notes: string | null; fileIds: Uuid[] };